@@ -0,0 +1,28 @@
+use cat2verilog::ast::CategoryAST;
+use cat2verilog::dag::CategoryDAG;
+use cat2verilog::parser::parse_category_file;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/// Build a chain-shaped category with roughly `statements` statements:
+/// one object per stage and a morphism from each stage to the next
+fn generate_category(statements: usize) -> CategoryAST {
+    let stages = statements / 2;
+    let mut source = String::new();
+    for i in 0..stages {
+        source.push_str(&format!("object S{}\n", i));
+    }
+    for i in 1..stages {
+        source.push_str(&format!("morphism f{}: S{} -> S{} = in + 1\n", i, i - 1, i));
+    }
+    parse_category_file(&source).unwrap().1
+}
+
+fn bench_from_ast(c: &mut Criterion) {
+    let ast = generate_category(50_000);
+    c.bench_function("from_ast 50k statements", |b| {
+        b.iter(|| CategoryDAG::from_ast(black_box(&ast)).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_from_ast);
+criterion_main!(benches);
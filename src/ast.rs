@@ -1,29 +1,1496 @@
-use std::collections::HashMap;
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+// Ordered collections stand in for the hashed ones without `std`
+#[cfg(not(feature = "std"))]
+use alloc::collections::{BTreeMap as HashMap, BTreeSet as HashSet};
+
+/// Width used for objects without a width annotation
+pub const DEFAULT_WIDTH: usize = 8;
+
+/// Turn a DSL name into a legal Verilog identifier by replacing every
+/// character Verilog doesn't allow with `_`
+pub fn verilog_identifier(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if !ident.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        ident.insert(0, '_');
+    }
+    ident
+}
+
+/// Keywords that may follow an object's name or width, or structure a
+/// conditional body, so a plain name spelled like one would be read back as
+/// the keyword
+const OBJECT_KEYWORDS: &[&str] = &["signed", "active_low", "if", "then", "else"];
+
+/// Print a name in DSL syntax, quoting it when it isn't a plain identifier
+pub fn source_identifier(name: &str) -> String {
+    let mut chars = name.chars();
+    let plain = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !OBJECT_KEYWORDS.contains(&name);
+    if plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name)
+    }
+}
+
+/// Width annotation expression: object Sum : A + 1
+#[derive(Debug, Clone, PartialEq)]
+pub enum WidthExpr {
+    /// Literal bit count
+    Const(usize),
+    /// Width of another object
+    Ref(String),
+    Add(Box<WidthExpr>, Box<WidthExpr>),
+    Mul(Box<WidthExpr>, Box<WidthExpr>),
+    /// Wider of two widths: max(A, B)
+    Max(Box<WidthExpr>, Box<WidthExpr>),
+}
+
+impl WidthExpr {
+    /// Print the expression in DSL syntax
+    pub fn to_source(&self) -> String {
+        match self {
+            WidthExpr::Const(n) => n.to_string(),
+            WidthExpr::Ref(name) => source_identifier(name),
+            WidthExpr::Add(lhs, rhs) => {
+                let rhs = match rhs.as_ref() {
+                    WidthExpr::Add(..) => format!("({})", rhs.to_source()),
+                    _ => rhs.to_source(),
+                };
+                format!("{} + {}", lhs.to_source(), rhs)
+            }
+            WidthExpr::Mul(lhs, rhs) => {
+                let lhs = match lhs.as_ref() {
+                    WidthExpr::Add(..) => format!("({})", lhs.to_source()),
+                    _ => lhs.to_source(),
+                };
+                let rhs = match rhs.as_ref() {
+                    WidthExpr::Add(..) | WidthExpr::Mul(..) => format!("({})", rhs.to_source()),
+                    _ => rhs.to_source(),
+                };
+                format!("{} * {}", lhs, rhs)
+            }
+            WidthExpr::Max(lhs, rhs) => format!("max({}, {})", lhs.to_source(), rhs.to_source()),
+        }
+    }
+
+    /// Apply `rename` to every object referenced by the expression
+    fn rename_refs(&mut self, rename: &dyn Fn(&mut String)) {
+        match self {
+            WidthExpr::Const(_) => {}
+            WidthExpr::Ref(name) => rename(name),
+            WidthExpr::Add(lhs, rhs) | WidthExpr::Mul(lhs, rhs) | WidthExpr::Max(lhs, rhs) => {
+                lhs.rename_refs(rename);
+                rhs.rename_refs(rename);
+            }
+        }
+    }
+
+    /// Evaluate against already resolved object widths
+    pub fn evaluate(&self, widths: &HashMap<String, usize>) -> Result<usize, String> {
+        match self {
+            WidthExpr::Const(n) => Ok(*n),
+            WidthExpr::Ref(name) => widths
+                .get(name)
+                .copied()
+                .ok_or_else(|| format!("Width annotation references unknown object {}", name)),
+            WidthExpr::Add(lhs, rhs) => Ok(lhs.evaluate(widths)? + rhs.evaluate(widths)?),
+            WidthExpr::Mul(lhs, rhs) => Ok(lhs.evaluate(widths)? * rhs.evaluate(widths)?),
+            WidthExpr::Max(lhs, rhs) => Ok(lhs.evaluate(widths)?.max(rhs.evaluate(widths)?)),
+        }
+    }
+}
+
+/// Binary operator in a morphism body expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Shl,
+    Shr,
+    And,
+    Or,
+    Xor,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl BinOp {
+    /// Operator symbol, shared by the DSL and Verilog
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Shl => "<<",
+            BinOp::Shr => ">>",
+            BinOp::And => "&",
+            BinOp::Or => "|",
+            BinOp::Xor => "^",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+        }
+    }
+
+    /// Whether the operator compares its operands, giving a single bit
+    pub fn is_comparison(&self) -> bool {
+        matches!(self, BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge)
+    }
+
+    /// Binding strength, following Verilog; higher binds tighter
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinOp::Or => 1,
+            BinOp::Xor => 2,
+            BinOp::And => 3,
+            BinOp::Eq | BinOp::Ne => 4,
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 5,
+            BinOp::Shl | BinOp::Shr => 6,
+            BinOp::Add | BinOp::Sub => 7,
+            BinOp::Mul => 8,
+        }
+    }
+}
+
+/// Morphism body expression: in * 3 + 1
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// Signal reference; `in` names the morphism's input
+    Ident(String),
+    /// Unsized integer literal
+    Literal(u64),
+    /// Part select of a signal: in[7:4]
+    Slice {
+        name: String,
+        msb: usize,
+        lsb: usize,
+    },
+    /// Bitwise complement, binding tighter than any binary operator: ~in
+    Not(Box<Expr>),
+    Binary {
+        op: BinOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    /// Concatenation, the first part in the upper bits: {in[3:0], in[7:4]}
+    Concat(Vec<Expr>),
+    /// Choice between two values, a ternary in Verilog:
+    /// if in > 10 then in - 10 else in
+    If {
+        condition: Box<Expr>,
+        then: Box<Expr>,
+        otherwise: Box<Expr>,
+    },
+}
+
+impl Expr {
+    fn rename_refs(&mut self, rename: &dyn Fn(&mut String)) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Ident(name) | Expr::Slice { name, .. } => rename(name),
+            Expr::Binary { lhs, rhs, .. } => {
+                lhs.rename_refs(rename);
+                rhs.rename_refs(rename);
+            }
+            Expr::Not(operand) => operand.rename_refs(rename),
+            Expr::Concat(parts) => parts.iter_mut().for_each(|part| part.rename_refs(rename)),
+            Expr::If { condition, then, otherwise } => {
+                condition.rename_refs(rename);
+                then.rename_refs(rename);
+                otherwise.rename_refs(rename);
+            }
+        }
+    }
+
+    /// Names of the signals the expression reads, in order of appearance
+    pub fn signals(&self) -> Vec<&str> {
+        match self {
+            Expr::Literal(_) => Vec::new(),
+            Expr::Ident(name) | Expr::Slice { name, .. } => vec![name.as_str()],
+            Expr::Binary { lhs, rhs, .. } => {
+                let mut signals = lhs.signals();
+                signals.extend(rhs.signals());
+                signals
+            }
+            Expr::Not(operand) => operand.signals(),
+            Expr::Concat(parts) => parts.iter().flat_map(Expr::signals).collect(),
+            Expr::If { condition, then, otherwise } => {
+                let mut signals = condition.signals();
+                signals.extend(then.signals());
+                signals.extend(otherwise.signals());
+                signals
+            }
+        }
+    }
+
+    /// Render the expression as Verilog with `rename` applied to every identifier
+    pub fn render(&self, rename: &dyn Fn(&str) -> String) -> String {
+        self.render_shifts(rename, false, false)
+    }
+
+    /// Render as `render` does, writing `>>` as the sign-extending `>>>`
+    /// when `arithmetic` is set and Verilog treats the shifted operand as
+    /// signed, and conditionals in DSL rather than Verilog syntax when
+    /// `source` is
+    fn render_shifts(&self, rename: &dyn Fn(&str) -> String, arithmetic: bool, source: bool) -> String {
+        // A conditional binds looser than any operator
+        let operand = |expr: &Expr| match expr {
+            Expr::If { .. } => format!("({})", expr.render_shifts(rename, arithmetic, source)),
+            _ => expr.render_shifts(rename, arithmetic, source),
+        };
+        match self {
+            Expr::Ident(name) => rename(name),
+            Expr::Literal(value) => value.to_string(),
+            Expr::Slice { name, msb, lsb } => format!("{}[{}:{}]", rename(name), msb, lsb),
+            Expr::Not(operand) => match operand.as_ref() {
+                Expr::Binary { .. } | Expr::If { .. } => format!("~({})", operand.render_shifts(rename, arithmetic, source)),
+                _ => format!("~{}", operand.render_shifts(rename, arithmetic, source)),
+            },
+            Expr::Binary { op, lhs, rhs } => {
+                let lhs_str = operand(lhs);
+                let rhs_str = operand(rhs);
+                // Left operands only need parens when they bind looser; right
+                // operands also need them at equal precedence since `-` isn't associative
+                let lhs_str = match lhs.as_ref() {
+                    Expr::Binary { op: inner, .. } if inner.precedence() < op.precedence() => {
+                        format!("({})", lhs_str)
+                    }
+                    _ => lhs_str,
+                };
+                let rhs_str = match rhs.as_ref() {
+                    Expr::Binary { op: inner, .. } if inner.precedence() <= op.precedence() => {
+                        format!("({})", rhs_str)
+                    }
+                    _ => rhs_str,
+                };
+                let symbol = match op {
+                    BinOp::Shr if arithmetic && lhs.signed_in_verilog() => ">>>",
+                    _ => op.symbol(),
+                };
+                format!("{} {} {}", lhs_str, symbol, rhs_str)
+            }
+            Expr::Concat(parts) => {
+                let parts: Vec<String> = parts.iter().map(|part| part.render_shifts(rename, arithmetic, source)).collect();
+                format!("{{{}}}", parts.join(", "))
+            }
+            // Only a conditional in the last branch chains without parentheses
+            Expr::If { condition, then, otherwise } => {
+                let otherwise = otherwise.render_shifts(rename, arithmetic, source);
+                match source {
+                    true => format!("if {} then {} else {}", operand(condition), operand(then), otherwise),
+                    false => format!("{} ? {} : {}", operand(condition), operand(then), otherwise),
+                }
+            }
+        }
+    }
+
+    /// Print the expression in DSL syntax
+    pub fn to_source(&self) -> String {
+        self.render_shifts(&source_identifier, false, true)
+    }
+
+    /// Infer the width of the expression's full result, keeping carry bits:
+    /// `+`/`-` grow by one bit over the wider operand, `*` sums its operand
+    /// widths, a left shift by a constant grows by the shift amount and a
+    /// concatenation is as wide as its parts together.
+    /// Returns `None` if any referenced signal is unknown.
+    pub fn infer_width(&self, signal_width: &dyn Fn(&str) -> Option<usize>) -> Option<usize> {
+        match self {
+            Expr::Ident(name) => signal_width(name),
+            Expr::Literal(value) => Some((u64::BITS - value.leading_zeros()).max(1) as usize),
+            Expr::Slice { msb, lsb, .. } => Some(msb - lsb + 1),
+            Expr::Binary { op, lhs, rhs } => {
+                let shift = match rhs.as_ref() {
+                    Expr::Literal(amount) => usize::try_from(*amount).ok(),
+                    _ => None,
+                };
+                let lhs = lhs.infer_width(signal_width)?;
+                let rhs = rhs.infer_width(signal_width)?;
+                Some(match op {
+                    BinOp::Add | BinOp::Sub => lhs.max(rhs) + 1,
+                    BinOp::Mul => lhs + rhs,
+                    BinOp::Shl => lhs.saturating_add(shift.unwrap_or(0)),
+                    BinOp::Shr => lhs,
+                    BinOp::And | BinOp::Or | BinOp::Xor => lhs.max(rhs),
+                    _ => 1,
+                })
+            }
+            Expr::Not(operand) => operand.infer_width(signal_width),
+            Expr::Concat(parts) => parts.iter().map(|part| part.infer_width(signal_width)).sum(),
+            Expr::If { then, otherwise, .. } => Some(then.infer_width(signal_width)?.max(otherwise.infer_width(signal_width)?)),
+        }
+    }
+
+    /// Lower to a Verilog expression, mapping `in` to the given input port
+    pub fn to_verilog(&self, input_port: &str) -> String {
+        self.render_shifts(&|name| port_or_identifier(name, input_port), false, false)
+    }
+
+    /// Lower as `to_verilog` does for a signed input, whose right shifts
+    /// extend the sign bit. Part-selects, concatenations and comparisons
+    /// are unsigned in Verilog, so shifts of them stay logical
+    pub fn to_signed_verilog(&self, input_port: &str) -> String {
+        self.render_shifts(&|name| port_or_identifier(name, input_port), true, false)
+    }
+
+    /// Whether Verilog evaluates the expression as signed when every signal
+    /// it reads is declared signed
+    fn signed_in_verilog(&self) -> bool {
+        match self {
+            Expr::Ident(_) | Expr::Literal(_) => true,
+            Expr::Slice { .. } | Expr::Concat(_) => false,
+            Expr::Not(operand) => operand.signed_in_verilog(),
+            Expr::Binary { op, .. } if op.is_comparison() => false,
+            Expr::Binary { op: BinOp::Shl | BinOp::Shr, lhs, .. } => lhs.signed_in_verilog(),
+            Expr::Binary { lhs, rhs, .. } => lhs.signed_in_verilog() && rhs.signed_in_verilog(),
+            Expr::If { then, otherwise, .. } => then.signed_in_verilog() && otherwise.signed_in_verilog(),
+        }
+    }
+}
+
+/// Verilog name of a signal a body reads, `in` being the input port
+fn port_or_identifier(name: &str, input_port: &str) -> String {
+    if name == "in" {
+        input_port.to_string()
+    } else {
+        verilog_identifier(name)
+    }
+}
+
+/// Composition of morphisms as an assertion writes it, keeping its
+/// grouping: (h ∘ g) ∘ f
+#[derive(Debug, Clone, PartialEq)]
+pub enum Composition {
+    /// A single morphism, or `id`
+    Morphism(String),
+    /// The outer morphism applied after the inner one: g ∘ f
+    Compose(Box<Composition>, Box<Composition>),
+}
+
+impl Composition {
+    /// Composition of a chain, outermost morphism first, grouped to the
+    /// right. The chain must not be empty
+    pub fn from_chain(chain: Vec<String>) -> Self {
+        chain
+            .into_iter()
+            .map(Composition::Morphism)
+            .rev()
+            .reduce(|inner, outer| Composition::Compose(Box::new(outer), Box::new(inner)))
+            .unwrap_or_else(|| Composition::Morphism(String::new()))
+    }
+
+    /// Morphisms applied, outermost first. Composition is associative, so
+    /// the grouping doesn't change the chain
+    pub fn chain(&self) -> Vec<String> {
+        match self {
+            Composition::Morphism(name) => vec![name.clone()],
+            Composition::Compose(outer, inner) => {
+                let mut chain = outer.chain();
+                chain.extend(inner.chain());
+                chain
+            }
+        }
+    }
+
+    /// Resolve the names written with `.`, see `chain_names`
+    fn resolve(&mut self, declared: &dyn Fn(&str) -> bool) {
+        match self {
+            Composition::Morphism(name) => {
+                let chain = chain_names(name, declared);
+                *self = Composition::from_chain(chain);
+            }
+            Composition::Compose(outer, inner) => {
+                outer.resolve(declared);
+                inner.resolve(declared);
+            }
+        }
+    }
+
+    fn rename_refs(&mut self, rename: &dyn Fn(&mut String)) {
+        match self {
+            Composition::Morphism(name) => rename(name),
+            Composition::Compose(outer, inner) => {
+                outer.rename_refs(rename);
+                inner.rename_refs(rename);
+            }
+        }
+    }
+
+    /// Print the composition in DSL syntax; only an outer part that is
+    /// itself a composition needs parentheses
+    pub fn to_source(&self) -> String {
+        match self {
+            Composition::Morphism(name) => chain_source_identifier(name),
+            Composition::Compose(outer, inner) => match outer.as_ref() {
+                Composition::Compose(..) => format!("({}) ∘ {}", outer.to_source(), inner.to_source()),
+                Composition::Morphism(_) => format!("{} ∘ {}", outer.to_source(), inner.to_source()),
+            },
+        }
+    }
+}
+
+/// Option a backend may honor, written before a morphism: @pipeline(2), @keep
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub name: String,
+    /// Numbers or names between the parentheses
+    pub args: Vec<String>,
+}
+
+impl Attribute {
+    /// Print the attribute in DSL syntax
+    pub fn to_source(&self) -> String {
+        match self.args.is_empty() {
+            true => format!("@{}", self.name),
+            false => format!("@{}({})", self.name, self.args.join(", ")),
+        }
+    }
+}
 
 /// AST node representing a category theory statement
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
-    /// Object declaration: object A
-    Object(String),
+    /// Object declaration: object A, optionally with a width: object A : 16
+    Object {
+        name: String,
+        width: Option<WidthExpr>,
+        /// Two's complement value: object A : signed 16
+        signed: bool,
+        /// Simulation-only start value of a register: object A : 8 = 8'h3C
+        initial: Option<String>,
+        /// Asserted when low: object rst_n : 1 active_low
+        active_low: bool,
+        /// Fraction bits of a fixed-point value, counted in its width along
+        /// with the integer bits: object A : fixed<8,4>
+        fraction: Option<usize>,
+        /// Clock domain the object belongs to: object A @ clk_a
+        domain: Option<String>,
+    },
+    /// Family of objects sharing one type, one per index of an inclusive
+    /// range, named `A_0` to `A_7` and written `A[3]` elsewhere:
+    /// object A[0..7] : 8
+    ObjectFamily {
+        name: String,
+        start: usize,
+        end: usize,
+        width: Option<WidthExpr>,
+        signed: bool,
+        fraction: Option<usize>,
+    },
     /// Morphism declaration: morphism f: A -> B
     Morphism { 
         name: String, 
         from: String, 
-        to: String 
+        to: String,
+        /// Width the morphism expects of its source: morphism f: A : 8 -> B
+        from_width: Option<WidthExpr>,
+        /// Width the morphism expects of its target: morphism f: A -> B : 16
+        to_width: Option<WidthExpr>,
+        /// Combinational logic: morphism f: A -> B = in + 1
+        body: Option<Expr>,
+        /// Named intermediate values the body may read, each generated as a
+        /// wire: morphism f: A -> B { let t = in >> 2; out = t + in }
+        bindings: Bindings,
+        /// Number of parallel copies: morphism lane[0..8]: A -> B
+        lanes: Option<usize>,
+        /// Clocked register stage: seq morphism f: A -> B
+        registered: bool,
+        /// Macro the generated logic is conditional on: morphism f: A -> B @ifdef DEBUG
+        guard: Option<String>,
+        /// Verilog file of a black box implementing the morphism instead of
+        /// generated logic: morphism f: A -> B extern "f_impl.v"
+        external: Option<String>,
+        /// Valid signal travelling with the data: morphism f: A -> B @handshake
+        handshake: bool,
+        /// Kleisli morphism over the option monad, a handshake whose output
+        /// is zero while the input is invalid: morphism f: maybe A -> maybe B
+        maybe: bool,
+        /// Width parameter both endpoints share, generated as a module
+        /// parameter: morphism f<W>: A<W> -> B<W>
+        generic: Option<String>,
+        /// Backend options: @pipeline(2) morphism f: A -> B
+        attributes: Vec<Attribute>,
+    },
+    /// Named connection between morphisms, never a port: wire mid : 8
+    Wire {
+        name: String,
+        width: Option<WidthExpr>,
+    },
+    /// Interface declaration: interface Bus { data: 32, valid: 1 }
+    Interface {
+        name: String,
+        fields: Vec<(String, usize)>, // (field, width)
+    },
+    /// Commutativity assertion: assert commute: (h ∘ g) ∘ f == h ∘ (g ∘ f)
+    AssertCommute {
+        lhs: Composition,
+        rhs: Composition,
+    },
+    /// Equality of two parallel morphisms: assert equal: f == g
+    AssertEqual {
+        lhs: String,
+        rhs: String,
+    },
+    /// Claim that a morphism is mono or epi: assert mono f, assert epi g
+    AssertKind {
+        morphism: String,
+        kind: MorphismKind,
+    },
+    /// Isomorphism, a morphism `name: from -> to` together with its inverse
+    /// `inverse: to -> from`: iso f: A <-> B = in + 1, in - 1
+    Iso {
+        name: String,
+        /// Always `<name>_inv`
+        inverse: String,
+        from: String,
+        to: String,
+        body: Option<Expr>,
+        inverse_body: Option<Expr>,
+    },
+    /// Top module parameter usable in widths: param WIDTH = 8
+    Param {
+        name: String,
+        value: usize,
     },
-    /// Commutativity assertion: assert commute: g ∘ f == h
-    AssertCommute { 
-        lhs: Vec<String>, 
-        rhs: Vec<String> 
+    /// Composite morphism computed by inlining a chain, outermost first,
+    /// with optional widths of the signals between them: define h = g ∘[4] f
+    Define {
+        name: String,
+        chain: Vec<String>,
+        /// Width of the signal from `chain[i + 1]` into `chain[i]`, if overridden
+        junction_widths: Vec<Option<usize>>,
     },
+    /// Test vector checked by the generated testbench, as Verilog numbers:
+    /// test f: in 8'd5 => out 8'd6
+    Test {
+        morphism: String,
+        input: String,
+        expected: String,
+    },
+    /// Identity on an object, carried by a pass-through wire: id A, which
+    /// is named id_A, or morphism i := identity A
+    Identity {
+        name: String,
+        object: String,
+    },
+    /// Product object, carried as the concatenation of its components with
+    /// the first one in the upper bits: object C = A × B
+    Product {
+        name: String,
+        components: Vec<String>,
+    },
+    /// Coproduct object, carried as a tag above the widest component's
+    /// data: object C = A + B
+    Coproduct {
+        name: String,
+        components: Vec<String>,
+    },
+    /// Enumerated object, its members encoded in binary in declaration
+    /// order: object State = { IDLE, RUN, DONE }
+    Enum {
+        name: String,
+        members: Vec<String>,
+    },
+    /// Memory object holding `depth` words, carried between the morphism
+    /// writing it and those reading it as a write request of enable,
+    /// address and data: object Mem : ram 8 x 256
+    Memory {
+        name: String,
+        width: WidthExpr,
+        depth: usize,
+    },
+    /// Case analysis out of a coproduct, muxing one branch per component:
+    /// morphism h: C -> D = [f, g]
+    Case {
+        name: String,
+        from: String,
+        to: String,
+        branches: Vec<String>,
+    },
+    /// Morphism given by a truth table, one row per input pattern, lowered
+    /// to a casez block: morphism dec: A -> B table { 00 -> 01, 1? -> 10 }
+    Table {
+        name: String,
+        from: String,
+        to: String,
+        rows: TruthTable,
+    },
+    /// Morphism written directly in Verilog, whose text names its ports `in`
+    /// and `out`: morphism f: A -> B verilog { assign out = ^in; }
+    Verilog {
+        name: String,
+        from: String,
+        to: String,
+        text: String,
+    },
+    /// Morphism looking its result up in a ROM loaded from a hex file, one
+    /// word per address: morphism lut: Addr -> Data rom "table.hex"
+    Rom {
+        name: String,
+        from: String,
+        to: String,
+        path: String,
+    },
+    /// Morphism reading several objects, one input port each, whose body
+    /// names the objects it reads: morphism add: (A, B) -> C = A + B
+    MultiInput {
+        name: String,
+        from: Vec<String>,
+        to: String,
+        body: Option<Expr>,
+        /// Clocked register stage: seq morphism acc: (A, B) -> C = A + B
+        registered: bool,
+    },
+    /// Morphism from the built-in library, reading its arguments in order:
+    /// morphism s = std.add(A, B) -> C
+    Library {
+        name: String,
+        /// One of `STD_MORPHISMS`, checked when the DAG is built
+        element: String,
+        from: Vec<String>,
+        to: String,
+    },
+    /// Literal value driven onto an object, a morphism from the unit
+    /// object: morphism zero: I -> A = 8'h00
+    Constant {
+        name: String,
+        to: String,
+        value: String,
+    },
+    /// Morphisms run side by side between the products of their sources
+    /// and of their targets: morphism p = f ⊗ g
+    Tensor {
+        name: String,
+        factors: Vec<String>,
+    },
+    /// Braiding of a two-component product, exchanging its halves by
+    /// rewiring alone: morphism s = swap(A, B) reads A × B and writes B × A
+    Swap {
+        name: String,
+        first: String,
+        second: String,
+    },
+    /// Copy of an object onto both halves of its square, making fan-out
+    /// explicit: morphism d = dup(A) reads A and writes A × A
+    Dup {
+        name: String,
+        object: String,
+    },
+    /// Projection of a product onto one of its components, counted from 1,
+    /// by slicing its bus: morphism p = pi1(C) reads A × B and writes A
+    Projection {
+        name: String,
+        product: String,
+        index: usize,
+    },
+    /// Injection of one side of a two-component coproduct, setting its tag:
+    /// morphism i = inl(C) reads A and morphism j = inr(C) reads B of A + B
+    Injection {
+        name: String,
+        coproduct: String,
+        /// Injects the second component, `inr`
+        right: bool,
+    },
+    /// Feedback around a morphism reading its own target: the loop is cut
+    /// by a register holding that object, and the other inputs remain:
+    /// morphism fsm = trace(step)
+    Trace {
+        name: String,
+        step: String,
+    },
+    /// Map from one section into another: every morphism of `from`
+    /// reappears in `to`, reusing its module, between the images of its
+    /// endpoints. Unmapped names keep their own: functor F: alu -> core { A => X }
+    Functor {
+        name: String,
+        from: String,
+        to: String,
+        mapping: FunctorMapping,
+    },
+    /// Natural transformation between two functors with the same source and
+    /// target sections: one adapter morphism F(X) -> G(X) per object X,
+    /// passing its input through unless given a body:
+    /// nat eta: F => G { X = in + 1 }
+    Nat {
+        name: String,
+        from: String,
+        to: String,
+        components: NatComponents,
+    },
+    /// Statements of another file, resolved relative to this one and
+    /// prefixed like a section when given an alias:
+    /// import "lib/adders.cat" as adders
+    Import {
+        path: String,
+        alias: Option<String>,
+    },
+    /// Statements reused by every `use` of the template, with its parameters
+    /// replaced by the arguments of the use:
+    /// template pipeline(X, n) { for i in 1..n { morphism s[i]: X -> X = in } }
+    Template {
+        name: String,
+        params: Vec<String>,
+        /// DSL text, parsed once the arguments are filled in
+        body: String,
+    },
+    /// Statements of a template for the given names and integers, prefixed
+    /// like a section when given an alias: use pipeline(A, 4) as p1
+    Use {
+        template: String,
+        args: Vec<String>,
+        alias: Option<String>,
+    },
+    /// Clock domain objects can be placed in: clock clk_a
+    Clock {
+        name: String,
+    },
+    /// Name of the generated top module, `top` unless given: top MyDesign
+    Top {
+        name: String,
+    },
+    /// Verilog attribute written verbatim before the module of a morphism,
+    /// or the declarations of the signals carrying an object:
+    /// pragma f "(* keep_hierarchy = \"yes\" *)"
+    Pragma {
+        target: String,
+        text: String,
+    },
+    /// Block whose declarations are prefixed like a section and whose
+    /// morphisms are instantiated together in a module of its own. The
+    /// statements of the block follow it already prefixed:
+    /// category Alu { morphism add: A -> B }
+    Category {
+        name: String,
+    },
+}
+
+impl Statement {
+    /// Print the statement in DSL syntax
+    pub fn to_source(&self) -> String {
+        match self {
+            Statement::Object { name, width, signed, initial, active_low, fraction, domain } => {
+                let mut source = format!("object {}{}", source_identifier(name), type_source(width, *signed, *fraction));
+                if *active_low {
+                    source.push_str(" active_low");
+                }
+                if let Some(domain) = domain {
+                    source.push_str(&format!(" @ {}", source_identifier(domain)));
+                }
+                if let Some(initial) = initial {
+                    source.push_str(&format!(" = {}", initial));
+                }
+                source
+            }
+            Statement::ObjectFamily { name, start, end, width, signed, fraction } => {
+                format!("object {}[{}..{}]{}", source_identifier(name), start, end, type_source(width, *signed, *fraction))
+            }
+            Statement::Morphism { name, from, to, from_width, to_width, body, bindings, lanes, registered, guard, external, handshake, maybe, generic, attributes } => {
+                let mut source = String::new();
+                for attribute in attributes {
+                    source.push_str(&format!("{} ", attribute.to_source()));
+                }
+                if *registered {
+                    source.push_str("seq ");
+                }
+                source.push_str(&format!("morphism {}", source_identifier(name)));
+                if let Some(lanes) = lanes {
+                    source.push_str(&format!("[0..{}]", lanes));
+                }
+                let parameter = generic.as_ref().map(|generic| format!("<{}>", generic)).unwrap_or_default();
+                source.push_str(&parameter);
+                let modifier = if *maybe { "maybe " } else { "" };
+                let endpoint = |object: &str, width: &Option<WidthExpr>| match width {
+                    Some(width) => format!("{}{} : {}", modifier, source_identifier(object), width.to_source()),
+                    None => format!("{}{}{}", modifier, source_identifier(object), parameter),
+                };
+                source.push_str(&format!(": {} -> {}", endpoint(from, from_width), endpoint(to, to_width)));
+                match body {
+                    Some(body) if !bindings.is_empty() => {
+                        let bindings: Vec<String> = bindings
+                            .iter()
+                            .map(|(binding, expr)| format!("let {} = {}; ", binding, expr.to_source()))
+                            .collect();
+                        source.push_str(&format!(" {{ {}out = {} }}", bindings.concat(), body.to_source()));
+                    }
+                    Some(body) => source.push_str(&format!(" = {}", body.to_source())),
+                    None => {}
+                }
+                if let Some(external) = external {
+                    source.push_str(&format!(" extern \"{}\"", external));
+                }
+                if *handshake {
+                    source.push_str(" @handshake");
+                }
+                if let Some(guard) = guard {
+                    source.push_str(&format!(" @ifdef {}", guard));
+                }
+                source
+            }
+            Statement::Wire { name, width } => match width {
+                Some(width) => format!("wire {} : {}", source_identifier(name), width.to_source()),
+                None => format!("wire {}", source_identifier(name)),
+            },
+            Statement::Interface { name, fields } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, width)| format!("{}: {}", source_identifier(field), width))
+                    .collect();
+                format!("interface {} {{ {} }}", source_identifier(name), fields.join(", "))
+            }
+            Statement::AssertCommute { lhs, rhs } => {
+                format!("assert commute: {} == {}", lhs.to_source(), rhs.to_source())
+            }
+            Statement::AssertEqual { lhs, rhs } => {
+                format!("assert equal: {} == {}", source_identifier(lhs), source_identifier(rhs))
+            }
+            Statement::AssertKind { morphism, kind } => {
+                format!("assert {} {}", kind.keyword(), source_identifier(morphism))
+            }
+            Statement::Iso { name, from, to, body, inverse_body, .. } => {
+                let mut source = format!("iso {}: {} <-> {}", source_identifier(name), source_identifier(from), source_identifier(to));
+                if let (Some(body), Some(inverse_body)) = (body, inverse_body) {
+                    source.push_str(&format!(" = {}, {}", body.to_source(), inverse_body.to_source()));
+                }
+                source
+            }
+            Statement::Param { name, value } => format!("param {} = {}", source_identifier(name), value),
+            Statement::Define { name, chain, junction_widths } => {
+                format!("define {} = {}", source_identifier(name), composition_source(chain, junction_widths))
+            }
+            Statement::Test { morphism, input, expected } => {
+                format!("test {}: in {} => out {}", source_identifier(morphism), input, expected)
+            }
+            Statement::Identity { name, object } if *name == identity_name(object) => format!("id {}", source_identifier(object)),
+            Statement::Identity { name, object } => {
+                format!("morphism {} := identity {}", source_identifier(name), source_identifier(object))
+            }
+            Statement::Product { name, components } => {
+                let components: Vec<String> = components.iter().map(|component| source_identifier(component)).collect();
+                format!("object {} = {}", source_identifier(name), components.join(" × "))
+            }
+            Statement::Coproduct { name, components } => {
+                let components: Vec<String> = components.iter().map(|component| source_identifier(component)).collect();
+                format!("object {} = {}", source_identifier(name), components.join(" + "))
+            }
+            Statement::Enum { name, members } => format!("object {} = {{ {} }}", source_identifier(name), members.join(", ")),
+            Statement::Memory { name, width, depth } => format!("object {} : ram {} x {}", source_identifier(name), width.to_source(), depth),
+            Statement::Table { name, from, to, rows } => {
+                let rows: Vec<String> = rows.iter().map(|(pattern, value)| format!("{} -> {}", pattern, value)).collect();
+                format!(
+                    "morphism {}: {} -> {} table {{ {} }}",
+                    source_identifier(name),
+                    source_identifier(from),
+                    source_identifier(to),
+                    rows.join(", ")
+                )
+            }
+            Statement::Verilog { name, from, to, text } => format!(
+                "morphism {}: {} -> {} verilog {{ {} }}",
+                source_identifier(name),
+                source_identifier(from),
+                source_identifier(to),
+                text
+            ),
+            Statement::Rom { name, from, to, path } => {
+                format!("morphism {}: {} -> {} rom \"{}\"", source_identifier(name), source_identifier(from), source_identifier(to), path)
+            }
+            Statement::Case { name, from, to, branches } => {
+                let branches: Vec<String> = branches.iter().map(|branch| source_identifier(branch)).collect();
+                format!(
+                    "morphism {}: {} -> {} = [{}]",
+                    source_identifier(name),
+                    source_identifier(from),
+                    source_identifier(to),
+                    branches.join(", ")
+                )
+            }
+            Statement::MultiInput { name, from, to, body, registered } => {
+                let from: Vec<String> = from.iter().map(|object| source_identifier(object)).collect();
+                let mut source = format!("morphism {}: ({}) -> {}", source_identifier(name), from.join(", "), source_identifier(to));
+                if *registered {
+                    source.insert_str(0, "seq ");
+                }
+                if let Some(body) = body {
+                    source.push_str(&format!(" = {}", body.to_source()));
+                }
+                source
+            }
+            Statement::Library { name, element, from, to } => {
+                let from: Vec<String> = from.iter().map(|object| source_identifier(object)).collect();
+                format!("morphism {} = std.{}({}) -> {}", source_identifier(name), element, from.join(", "), source_identifier(to))
+            }
+            Statement::Constant { name, to, value } => {
+                format!("morphism {}: {} -> {} = {}", source_identifier(name), UNIT_OBJECT, source_identifier(to), value)
+            }
+            Statement::Tensor { name, factors } => {
+                let factors: Vec<String> = factors.iter().map(|factor| source_identifier(factor)).collect();
+                format!("morphism {} = {}", source_identifier(name), factors.join(" ⊗ "))
+            }
+            Statement::Swap { name, first, second } => {
+                format!("morphism {} = swap({}, {})", source_identifier(name), source_identifier(first), source_identifier(second))
+            }
+            Statement::Projection { name, product, index } => {
+                format!("morphism {} = pi{}({})", source_identifier(name), index, source_identifier(product))
+            }
+            Statement::Injection { name, coproduct, right } => {
+                let side = if *right { "inr" } else { "inl" };
+                format!("morphism {} = {}({})", source_identifier(name), side, source_identifier(coproduct))
+            }
+            Statement::Dup { name, object } => {
+                format!("morphism {} = dup({})", source_identifier(name), source_identifier(object))
+            }
+            Statement::Trace { name, step } => {
+                format!("morphism {} = trace({})", source_identifier(name), source_identifier(step))
+            }
+            Statement::Functor { name, from, to, mapping } => {
+                let mapping: Vec<String> = mapping
+                    .iter()
+                    .map(|(source, image)| format!("{} => {}", source_identifier(source), source_identifier(image)))
+                    .collect();
+                format!(
+                    "functor {}: {} -> {} {{ {} }}",
+                    source_identifier(name),
+                    source_identifier(from),
+                    source_identifier(to),
+                    mapping.join(", ")
+                )
+            }
+            Statement::Nat { name, from, to, components } => {
+                let mut source = format!("nat {}: {} => {}", source_identifier(name), source_identifier(from), source_identifier(to));
+                if !components.is_empty() {
+                    let components: Vec<String> = components
+                        .iter()
+                        .map(|(object, body)| format!("{} = {}", source_identifier(object), body.to_source()))
+                        .collect();
+                    source.push_str(&format!(" {{ {} }}", components.join(", ")));
+                }
+                source
+            }
+            Statement::Import { path, alias } => match alias {
+                Some(alias) => format!("import \"{}\" as {}", path, alias),
+                None => format!("import \"{}\"", path),
+            },
+            Statement::Template { name, params, body } => format!("template {}({}) {{ {} }}", name, params.join(", "), body),
+            Statement::Use { template, args, alias } => {
+                let args: Vec<String> = args.iter().map(|arg| template_argument(arg)).collect();
+                match alias {
+                    Some(alias) => format!("use {}({}) as {}", source_identifier(template), args.join(", "), alias),
+                    None => format!("use {}({})", source_identifier(template), args.join(", ")),
+                }
+            }
+            Statement::Clock { name } => format!("clock {}", source_identifier(name)),
+            Statement::Top { name } => format!("top {}", name),
+            Statement::Pragma { target, text } => {
+                format!("pragma {} \"{}\"", source_identifier(target), text.replace('\\', "\\\\").replace('"', "\\\""))
+            }
+            Statement::Category { name } => format!("category {} {{ }}", source_identifier(name)),
+        }
+    }
+
+    /// Apply `rename` to every name the statement declares or refers to,
+    /// except the names a functor maps, which belong to its sections
+    fn rename_names(&mut self, rename: &dyn Fn(&mut String)) {
+        match self {
+            Statement::Object { name, width, domain, .. } => {
+                rename(name);
+                if let Some(width) = width {
+                    width.rename_refs(rename);
+                }
+                if let Some(domain) = domain {
+                    rename(domain);
+                }
+            }
+            Statement::ObjectFamily { name, width, .. } => {
+                rename(name);
+                if let Some(width) = width {
+                    width.rename_refs(rename);
+                }
+            }
+            Statement::Wire { name, width } => {
+                rename(name);
+                if let Some(width) = width {
+                    width.rename_refs(rename);
+                }
+            }
+            Statement::Morphism { name, from, to, from_width, to_width, .. } => {
+                for name in [name, from, to] {
+                    rename(name);
+                }
+                for width in [from_width, to_width].into_iter().flatten() {
+                    width.rename_refs(rename);
+                }
+            }
+            Statement::Iso { name, inverse, from, to, .. } => {
+                for name in [name, inverse, from, to] {
+                    rename(name);
+                }
+            }
+            Statement::Interface { name, .. } | Statement::Param { name, .. } | Statement::Clock { name } | Statement::Category { name } => {
+                rename(name)
+            }
+            Statement::Define { name, chain, .. } => {
+                rename(name);
+                chain.iter_mut().for_each(rename);
+            }
+            Statement::AssertCommute { lhs, rhs } => {
+                lhs.rename_refs(rename);
+                rhs.rename_refs(rename);
+            }
+            Statement::AssertEqual { lhs, rhs } => {
+                rename(lhs);
+                rename(rhs);
+            }
+            Statement::Test { morphism, .. } | Statement::Pragma { target: morphism, .. } | Statement::AssertKind { morphism, .. } => rename(morphism),
+            Statement::Identity { name, object } => {
+                rename(name);
+                rename(object);
+            }
+            Statement::Product { name, components } | Statement::Coproduct { name, components } => {
+                rename(name);
+                components.iter_mut().for_each(rename);
+            }
+            // Members are values, not names
+            Statement::Enum { name, .. } => rename(name),
+            Statement::Memory { name, width, .. } => {
+                rename(name);
+                width.rename_refs(rename);
+            }
+            Statement::Case { name, from, to, branches } => {
+                rename(name);
+                rename(from);
+                rename(to);
+                branches.iter_mut().for_each(rename);
+            }
+            Statement::Table { name, from, to, .. } | Statement::Verilog { name, from, to, .. } | Statement::Rom { name, from, to, .. } => {
+                rename(name);
+                rename(from);
+                rename(to);
+            }
+            Statement::MultiInput { name, from, to, body, .. } => {
+                rename(name);
+                from.iter_mut().for_each(rename);
+                rename(to);
+                if let Some(body) = body {
+                    body.rename_refs(rename);
+                }
+            }
+            Statement::Library { name, from, to, .. } => {
+                rename(name);
+                from.iter_mut().for_each(rename);
+                rename(to);
+            }
+            Statement::Constant { name, to, .. } => {
+                rename(name);
+                rename(to);
+            }
+            Statement::Tensor { name, factors } => {
+                rename(name);
+                factors.iter_mut().for_each(rename);
+            }
+            Statement::Swap { name, first, second } => {
+                rename(name);
+                rename(first);
+                rename(second);
+            }
+            Statement::Trace { name, step } | Statement::Dup { name, object: step } | Statement::Projection { name, product: step, .. }
+            | Statement::Injection { name, coproduct: step, .. } => {
+                rename(name);
+                rename(step);
+            }
+            // Mapped names belong to the sections the functor connects
+            Statement::Functor { name, .. } => rename(name),
+            Statement::Nat { name, from, to, .. } => {
+                rename(name);
+                rename(from);
+                rename(to);
+            }
+            Statement::Template { name, .. } => rename(name),
+            Statement::Use { template, args, .. } => {
+                rename(template);
+                args.iter_mut().for_each(rename);
+            }
+            // A Verilog module name, outside the names of the description
+            Statement::Import { .. } | Statement::Top { .. } => {}
+        }
+    }
+
+    /// Names the statement declares
+    pub fn declared_names(&self) -> Vec<String> {
+        match self {
+            Statement::Object { name, .. }
+            | Statement::Morphism { name, .. }
+            | Statement::Wire { name, .. }
+            | Statement::Interface { name, .. }
+            | Statement::Param { name, .. }
+            | Statement::Define { name, .. }
+            | Statement::Identity { name, .. }
+            | Statement::Product { name, .. }
+            | Statement::Coproduct { name, .. }
+            | Statement::Enum { name, .. }
+            | Statement::Memory { name, .. }
+            | Statement::Case { name, .. }
+            | Statement::Table { name, .. }
+            | Statement::Verilog { name, .. }
+            | Statement::Rom { name, .. }
+            | Statement::MultiInput { name, .. }
+            | Statement::Library { name, .. }
+            | Statement::Constant { name, .. }
+            | Statement::Tensor { name, .. }
+            | Statement::Swap { name, .. }
+            | Statement::Dup { name, .. }
+            | Statement::Projection { name, .. }
+            | Statement::Injection { name, .. }
+            | Statement::Trace { name, .. }
+            | Statement::Functor { name, .. }
+            | Statement::Nat { name, .. }
+            | Statement::Clock { name }
+            | Statement::Template { name, .. }
+            | Statement::Category { name } => vec![name.clone()],
+            Statement::Iso { name, inverse, .. } => vec![name.clone(), inverse.clone()],
+            Statement::ObjectFamily { name, start, end, .. } => (*start..=*end).map(|index| family_member(name, index)).collect(),
+            Statement::AssertCommute { .. } | Statement::AssertEqual { .. } | Statement::AssertKind { .. } | Statement::Test { .. } | Statement::Import { .. } | Statement::Use { .. } | Statement::Top { .. } | Statement::Pragma { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Type annotation of an object in DSL syntax, with its leading ` : `
+/// unless the object is an unsigned default-width one
+fn type_source(width: &Option<WidthExpr>, signed: bool, fraction: Option<usize>) -> String {
+    let ty = match (width, fraction) {
+        (Some(WidthExpr::Const(width)), Some(fraction)) => Some(format!("fixed<{},{}>", width - fraction, fraction)),
+        (width, _) => width.as_ref().map(WidthExpr::to_source),
+    };
+    match (ty, signed) {
+        (Some(ty), true) => format!(" : signed {}", ty),
+        (Some(ty), false) => format!(" : {}", ty),
+        (None, true) => " : signed".to_string(),
+        (None, false) => String::new(),
+    }
+}
+
+/// Name of the object at `index` of a family: A_3 for A[3]
+pub fn family_member(name: &str, index: usize) -> String {
+    format!("{}_{}", name, index)
+}
+
+/// Names a functor renames, as (source, image)
+pub type FunctorMapping = Vec<(String, String)>;
+
+/// Rows of a truth table as (input pattern, output bits), most significant
+/// bit first, with `?` in a pattern matching either bit
+pub type TruthTable = Vec<(String, String)>;
+
+/// Values a morphism body binds by name before its result, in order
+pub type Bindings = Vec<(String, Expr)>;
+
+/// Property an assertion claims of a morphism: assert mono f
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MorphismKind {
+    /// Monomorphism: different inputs give different outputs
+    Mono,
+    /// Epimorphism: every output value is given by some input
+    Epi,
+}
+
+impl MorphismKind {
+    /// Keyword naming the kind in DSL syntax
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            MorphismKind::Mono => "mono",
+            MorphismKind::Epi => "epi",
+        }
+    }
+}
+
+/// Bodies of the components of a natural transformation, by object
+pub type NatComponents = Vec<(String, Expr)>;
+
+/// Object constants start from, which is never declared and carries no bits
+pub const UNIT_OBJECT: &str = "I";
+
+/// Object sinks discard into unless declared as an ordinary object, which
+/// carries no bits and is never a port
+pub const TERMINAL_OBJECT: &str = "T";
+
+/// Morphisms of the built-in library, with the number of objects each
+/// reads: `mux` reads a 1-bit select before the two values it picks from,
+/// `write` an enable, address and data and `read` a memory and an address
+pub const STD_MORPHISMS: [(&str, usize); 8] =
+    [("add", 2), ("mul", 2), ("mux", 3), ("reg", 1), ("cmp", 2), ("xor", 2), ("write", 3), ("read", 2)];
+
+/// Library morphisms `reduce` folds a family with, being associative
+pub const STD_REDUCTIONS: [&str; 3] = ["add", "mul", "xor"];
+
+/// Bit count of a sized Verilog literal: 8 for 8'h00, none for 60 or 'd60
+pub fn literal_width(literal: &str) -> Option<usize> {
+    literal.split_once('\'')?.0.parse().ok()
+}
+
+/// Bits of the tag telling the components of a coproduct apart
+pub fn tag_width(components: usize) -> usize {
+    (usize::BITS - components.saturating_sub(1).leading_zeros()).max(1) as usize
+}
+
+/// Name of the identity `id A` declares: id_A
+pub fn identity_name(object: &str) -> String {
+    format!("id_{}", object)
+}
+
+/// Print a composition chain with its junction widths: g ∘[4] f
+pub fn composition_source(chain: &[String], junction_widths: &[Option<usize>]) -> String {
+    let mut source = chain.first().map_or(String::new(), |outermost| chain_source_identifier(outermost));
+    for (morphism, width) in chain.iter().skip(1).zip(junction_widths) {
+        match width {
+            Some(width) => source.push_str(&format!(" ∘[{}] {}", width, chain_source_identifier(morphism))),
+            None => source.push_str(&format!(" ∘ {}", chain_source_identifier(morphism))),
+        }
+    }
+    source
+}
+
+/// Print a name of a composition chain, where `Alu.add` is left for
+/// `chain_names` to resolve
+fn chain_source_identifier(name: &str) -> String {
+    match name.contains('.') && name.split('.').all(|part| source_identifier(part) == part) {
+        true => name.to_string(),
+        false => source_identifier(name),
+    }
+}
+
+/// Names a name of a composition chain stands for, outermost first.
+/// `Alu.add` is the scoped morphism Alu_add when one is declared, and
+/// composes Alu after add otherwise; the longest declared scoped name is
+/// taken first, and a name declared as written is kept
+pub fn chain_names(name: &str, declared: &dyn Fn(&str) -> bool) -> Vec<String> {
+    if !name.contains('.') || declared(name) {
+        return vec![name.to_string()];
+    }
+    let parts: Vec<&str> = name.split('.').collect();
+    let mut names = Vec::new();
+    let mut start = 0;
+    while start < parts.len() {
+        let end = (start + 1..=parts.len())
+            .rev()
+            .find(|&end| end == start + 1 || declared(&parts[start..end].join("_")))
+            .unwrap_or(start + 1);
+        names.push(parts[start..end].join("_"));
+        start = end;
+    }
+    names
+}
+
+/// A composition chain with its names resolved by `chain_names`, and its
+/// junction widths with no width between the morphisms a name composes
+pub fn resolved_chain(chain: &[String], junction_widths: &[Option<usize>], declared: &dyn Fn(&str) -> bool) -> (Vec<String>, Vec<Option<usize>>) {
+    let (mut resolved, mut widths) = (Vec::new(), Vec::new());
+    for (i, name) in chain.iter().enumerate() {
+        if i > 0 {
+            widths.push(junction_widths.get(i - 1).copied().flatten());
+        }
+        for (j, part) in chain_names(name, declared).into_iter().enumerate() {
+            if j > 0 {
+                widths.push(None);
+            }
+            resolved.push(part);
+        }
+    }
+    (resolved, widths)
+}
+
+/// Prefix every name a group of statements declares, and the references to
+/// those names among them, with `<prefix>_`. Names declared outside the
+/// group are left alone: section alu { object A } declares alu_A
+pub fn namespaced(mut statements: Vec<Statement>, prefix: &str) -> Vec<Statement> {
+    let declared: HashSet<String> = statements.iter().flat_map(Statement::declared_names).collect();
+    let rename = |name: &mut String| {
+        if declared.contains(name.as_str()) {
+            *name = format!("{}_{}", prefix, name);
+        } else if name.contains('.') {
+            // A scoped name of a chain declared here is resolved now, and
+            // each morphism of a `.` composition is prefixed on its own
+            let scoped = name.replace('.', "_");
+            *name = match declared.contains(&scoped) {
+                true => format!("{}_{}", prefix, scoped),
+                false => name
+                    .split('.')
+                    .map(|part| match declared.contains(part) {
+                        true => format!("{}_{}", prefix, part),
+                        false => part.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("."),
+            };
+        }
+    };
+    for stmt in &mut statements {
+        match stmt {
+            Statement::ObjectFamily { name, width, .. } => {
+                // Only the members are declared, so the family name is
+                // prefixed whenever they are
+                *name = format!("{}_{}", prefix, name);
+                if let Some(width) = width {
+                    width.rename_refs(&rename);
+                }
+            }
+            // The imported file and the template's statements are
+            // declared under the section's prefix
+            Statement::Import { alias, .. } => {
+                *alias = Some(match alias.take() {
+                    Some(alias) => format!("{}_{}", prefix, alias),
+                    None => prefix.to_string(),
+                });
+            }
+            Statement::Use { template, args, alias } => {
+                rename(template);
+                args.iter_mut().for_each(rename);
+                *alias = Some(match alias.take() {
+                    Some(alias) => format!("{}_{}", prefix, alias),
+                    None => prefix.to_string(),
+                });
+            }
+            stmt => stmt.rename_names(&rename),
+        }
+    }
+    statements
+}
+
+/// Append a statement with object families replaced by their members and
+/// uses of templates by their statements. `active` holds the templates
+/// being expanded, so one that uses itself is caught
+fn expand(stmt: &Statement, templates: &HashMap<&str, (&[String], &str)>, active: &mut Vec<String>, statements: &mut Vec<Statement>) -> Result<(), String> {
+    match stmt {
+        Statement::ObjectFamily { name, start, end, width, signed, fraction } => {
+            statements.extend((*start..=*end).map(|index| Statement::Object {
+                name: family_member(name, index),
+                width: width.clone(),
+                signed: *signed,
+                initial: None,
+                active_low: false,
+                fraction: *fraction,
+                domain: None,
+            }));
+        }
+        Statement::Template { name, .. } => {
+            if let Some(outer) = active.last() {
+                return Err(format!("Template {} is declared inside template {}", name, outer));
+            }
+        }
+        Statement::Use { template, args, alias } => {
+            let (params, body) = templates.get(template.as_str()).ok_or_else(|| format!("Template {} not found", template))?;
+            if args.len() != params.len() {
+                return Err(format!("Template {} takes {} arguments, but a use of it passes {}", template, params.len(), args.len()));
+            }
+            if active.contains(template) {
+                return Err(format!("Template {} uses itself", template));
+            }
+            let text = substituted(body, params, args);
+            // The expanded text is what failed, so it goes into the error
+            let body = match crate::parser::parse_category_file(&text) {
+                Ok(("", ast)) => ast.statements,
+                Ok((rest, _)) => {
+                    return Err(format!(
+                        "Template {} does not parse with arguments ({}): unexpected input `{}` in\n{}",
+                        template, args.join(", "), rest.lines().next().unwrap_or(rest).trim(), text.trim()
+                    ));
+                }
+                Err(e) => return Err(format!("Template {} does not parse with arguments ({}): {} in\n{}", template, args.join(", "), e, text.trim())),
+            };
+            let body = match alias {
+                Some(alias) => namespaced(body, alias),
+                None => body,
+            };
+            active.push(template.clone());
+            for stmt in &body {
+                expand(stmt, templates, active, statements)?;
+            }
+            active.pop();
+        }
+        stmt => statements.push(stmt.clone()),
+    }
+    Ok(())
+}
+
+/// Body of a template with every parameter, as a whole word, replaced by
+/// its argument. String literals like file names are left alone
+fn substituted(body: &str, params: &[String], args: &[String]) -> String {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut text = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find(|c: char| is_ident(c) || c == '"') {
+        text.push_str(&rest[..start]);
+        let len = if rest[start..].starts_with('"') {
+            rest[start + 1..].find('"').map_or(rest.len() - start, |end| end + 2)
+        } else {
+            rest[start..].find(|c: char| !is_ident(c)).unwrap_or(rest.len() - start)
+        };
+        let token = &rest[start..start + len];
+        match params.iter().position(|param| param == token) {
+            Some(index) => text.push_str(&template_argument(&args[index])),
+            None => text.push_str(token),
+        }
+        rest = &rest[start + len..];
+    }
+    text.push_str(rest);
+    text
+}
+
+/// Print an argument of a template use in DSL syntax: an integer as it is,
+/// a name quoted when needed
+fn template_argument(arg: &str) -> String {
+    if !arg.is_empty() && arg.bytes().all(|b| b.is_ascii_digit()) {
+        arg.to_string()
+    } else {
+        source_identifier(arg)
+    }
+}
+
+/// Copy of the body of a loop for one index, with every name indexed by
+/// the loop variable naming that member: f[i] becomes f_3
+pub fn unrolled(statements: &[Statement], variable: &str, index: usize) -> Vec<Statement> {
+    let pattern = format!("[{}]", variable);
+    let member = format!("_{}", index);
+    let rename = |name: &mut String| {
+        if name.contains(&pattern) {
+            *name = name.replace(&pattern, &member);
+        }
+    };
+    let mut statements = statements.to_vec();
+    statements.iter_mut().for_each(|stmt| stmt.rename_names(&rename));
+    statements
 }
 
 /// Complete AST representing a category theory description
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CategoryAST {
     pub statements: Vec<Statement>,
 }
 
+impl Default for CategoryAST {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CategoryAST {
     pub fn new() -> Self {
         Self {
@@ -31,13 +1498,91 @@ impl CategoryAST {
         }
     }
 
-    /// Get all object names from the AST
+    /// Append the statements of another description, such as an imported
+    /// file, refusing any name both of them declare
+    pub fn merge(&mut self, other: CategoryAST) -> Result<(), String> {
+        let declared: HashSet<String> = self.statements.iter().flat_map(Statement::declared_names).collect();
+        if let Some(name) = other.statements.iter().flat_map(Statement::declared_names).find(|name| declared.contains(name)) {
+            return Err(format!("{} is already declared", name));
+        }
+        self.statements.extend(other.statements);
+        Ok(())
+    }
+
+    /// The description with every object family replaced by its members,
+    /// every use of a template by its statements and the names composition
+    /// chains write with `.` resolved, which is how everything past the
+    /// parser sees it
+    pub fn expanded(&self) -> Result<Cow<'_, CategoryAST>, String> {
+        let dotted = |chain: &[String]| chain.iter().any(|name| name.contains('.'));
+        let expandable = |stmt: &Statement| match stmt {
+            Statement::ObjectFamily { .. } | Statement::Template { .. } | Statement::Use { .. } => true,
+            Statement::Define { chain, .. } => dotted(chain),
+            Statement::AssertCommute { lhs, rhs } => dotted(&lhs.chain()) || dotted(&rhs.chain()),
+            _ => false,
+        };
+        if !self.statements.iter().any(expandable) {
+            return Ok(Cow::Borrowed(self));
+        }
+        let templates: HashMap<&str, (&[String], &str)> = self
+            .statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Template { name, params, body } => Some((name.as_str(), (&params[..], body.as_str()))),
+                _ => None,
+            })
+            .collect();
+        let mut statements = Vec::new();
+        for stmt in &self.statements {
+            expand(stmt, &templates, &mut Vec::new(), &mut statements)?;
+        }
+        // Only now is every name a chain may be scoped to declared
+        let declared: HashSet<String> = statements.iter().flat_map(Statement::declared_names).collect();
+        for stmt in &mut statements {
+            match stmt {
+                Statement::Define { chain, junction_widths, .. } => {
+                    (*chain, *junction_widths) = resolved_chain(chain, junction_widths, &|name| declared.contains(name));
+                }
+                Statement::AssertCommute { lhs, rhs } => {
+                    lhs.resolve(&|name| declared.contains(name));
+                    rhs.resolve(&|name| declared.contains(name));
+                }
+                _ => {}
+            }
+        }
+        Ok(Cow::Owned(CategoryAST { statements }))
+    }
+
+    /// Print the whole description in DSL syntax, one statement per line
+    pub fn to_source(&self) -> String {
+        self.statements
+            .iter()
+            .map(|stmt| format!("{}\n", stmt.to_source()))
+            .collect()
+    }
+
+    /// Get all object names from the AST, products and coproducts included
     pub fn get_objects(&self) -> Vec<&String> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Object { name, .. }
+                | Statement::Product { name, .. }
+                | Statement::Coproduct { name, .. }
+                | Statement::Enum { name, .. }
+                | Statement::Memory { name, .. } => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get all product objects as (name, components)
+    pub fn get_products(&self) -> Vec<(&String, &Vec<String>)> {
         self.statements
             .iter()
             .filter_map(|stmt| {
-                if let Statement::Object(name) = stmt {
-                    Some(name)
+                if let Statement::Product { name, components } = stmt {
+                    Some((name, components))
                 } else {
                     None
                 }
@@ -45,13 +1590,13 @@ impl CategoryAST {
             .collect()
     }
 
-    /// Get all morphism definitions as a map
-    pub fn get_morphisms(&self) -> HashMap<&String, (&String, &String)> {
+    /// Get all coproduct objects as (name, components)
+    pub fn get_coproducts(&self) -> Vec<(&String, &Vec<String>)> {
         self.statements
             .iter()
             .filter_map(|stmt| {
-                if let Statement::Morphism { name, from, to } = stmt {
-                    Some((name, (from, to)))
+                if let Statement::Coproduct { name, components } = stmt {
+                    Some((name, components))
                 } else {
                     None
                 }
@@ -59,17 +1604,807 @@ impl CategoryAST {
             .collect()
     }
 
-    /// Get all commutativity assertions
-    pub fn get_commute_assertions(&self) -> Vec<(&Vec<String>, &Vec<String>)> {
+    /// Get all enumerated objects as (name, members)
+    pub fn get_enums(&self) -> Vec<(&String, &Vec<String>)> {
         self.statements
             .iter()
             .filter_map(|stmt| {
-                if let Statement::AssertCommute { lhs, rhs } = stmt {
-                    Some((lhs, rhs))
+                if let Statement::Enum { name, members } = stmt {
+                    Some((name, members))
                 } else {
                     None
                 }
             })
             .collect()
     }
-} 
\ No newline at end of file
+
+    /// Get all memory objects as (name, word width, depth)
+    pub fn get_memories(&self) -> Vec<(&String, &WidthExpr, usize)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Memory { name, width, depth } = stmt {
+                    Some((name, width, *depth))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all case analyses as (name, branches)
+    pub fn get_cases(&self) -> Vec<(&String, &Vec<String>)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Case { name, branches, .. } = stmt {
+                    Some((name, branches))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all truth tables as (name, rows)
+    pub fn get_tables(&self) -> Vec<(&String, &TruthTable)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Table { name, rows, .. } = stmt {
+                    Some((name, rows))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all tensor products as (name, factors)
+    pub fn get_tensors(&self) -> Vec<(&String, &Vec<String>)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Tensor { name, factors } = stmt {
+                    Some((name, factors))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all swaps as (name, first, second)
+    pub fn get_swaps(&self) -> Vec<(&String, &String, &String)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Swap { name, first, second } = stmt {
+                    Some((name, first, second))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all duplications as (name, object)
+    pub fn get_dups(&self) -> Vec<(&String, &String)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Dup { name, object } = stmt {
+                    Some((name, object))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all projections as (name, product, index)
+    pub fn get_projections(&self) -> Vec<(&String, &String, usize)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Projection { name, product, index } = stmt {
+                    Some((name, product, *index))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all injections as (name, coproduct, right)
+    pub fn get_injections(&self) -> Vec<(&String, &String, bool)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Injection { name, coproduct, right } = stmt {
+                    Some((name, coproduct, *right))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all traces as (name, step)
+    pub fn get_traces(&self) -> Vec<(&String, &String)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Trace { name, step } = stmt {
+                    Some((name, step))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all multi-input morphisms as (name, sources, target, body)
+    pub fn get_multi_inputs(&self) -> Vec<(&String, &Vec<String>, &String, Option<&Expr>)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::MultiInput { name, from, to, body, .. } = stmt {
+                    Some((name, from, to, body.as_ref()))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all constant morphisms as (name, target, literal)
+    pub fn get_constants(&self) -> Vec<(&String, &String, &String)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Constant { name, to, value } = stmt {
+                    Some((name, to, value))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all library morphisms as (name, element, sources, target)
+    pub fn get_libraries(&self) -> Vec<(&String, &String, &Vec<String>, &String)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Library { name, element, from, to } = stmt {
+                    Some((name, element, from, to))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all clock domain names
+    pub fn get_clocks(&self) -> Vec<&String> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Clock { name } => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get all pragmas as (object or morphism, attribute text)
+    pub fn get_pragmas(&self) -> Vec<(&String, &String)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Pragma { target, text } => Some((target, text)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get every name given to the top module, which should be at most one
+    pub fn get_top_names(&self) -> Vec<&String> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Top { name } => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get all category block names, outer blocks before the ones they nest
+    pub fn get_categories(&self) -> Vec<&String> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Category { name } => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the objects placed in a clock domain as (object, clock)
+    pub fn get_domains(&self) -> Vec<(&String, &String)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Object { name, domain: Some(domain), .. } => Some((name, domain)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get all functors as (name, from, to, mapping)
+    pub fn get_functors(&self) -> Vec<(&String, &String, &String, &FunctorMapping)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Functor { name, from, to, mapping } = stmt {
+                    Some((name, from, to, mapping))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all natural transformations as (name, from, to, components)
+    pub fn get_nats(&self) -> Vec<(&String, &String, &String, &NatComponents)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Nat { name, from, to, components } = stmt {
+                    Some((name, from, to, components))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all morphism definitions as a map, including both halves of isos,
+    /// identities, compositions whose outermost and innermost morphisms are
+    /// known, tensors, swaps and duplications into declared products, and
+    /// projections out of them, and injections into declared coproducts
+    pub fn get_morphisms(&self) -> HashMap<&String, (&String, &String)> {
+        let mut morphisms: HashMap<&String, (&String, &String)> = self.statements
+            .iter()
+            .flat_map(|stmt| match stmt {
+                Statement::Morphism { name, from, to, .. }
+                | Statement::Case { name, from, to, .. }
+                | Statement::Table { name, from, to, .. }
+                | Statement::Verilog { name, from, to, .. }
+                | Statement::Rom { name, from, to, .. } => vec![(name, (from, to))],
+                Statement::Iso { name, inverse, from, to, .. } => vec![(name, (from, to)), (inverse, (to, from))],
+                Statement::Identity { name, object } => vec![(name, (object, object))],
+                _ => Vec::new(),
+            })
+            .collect();
+        for stmt in &self.statements {
+            let Statement::Define { name, chain, .. } = stmt else {
+                continue;
+            };
+            let innermost = chain.last().and_then(|m| morphisms.get(m));
+            let outermost = chain.first().and_then(|m| morphisms.get(m));
+            if let (Some(&(from, _)), Some(&(_, to))) = (innermost, outermost) {
+                morphisms.insert(name, (from, to));
+            }
+        }
+        let products = self.get_products();
+        let product = |components: Vec<&String>| {
+            products
+                .iter()
+                .find(|(_, declared)| declared.iter().eq(components.iter().copied()))
+                .map(|(name, _)| *name)
+        };
+        for (name, factors) in self.get_tensors() {
+            let Some(types) = factors.iter().map(|factor| morphisms.get(factor).copied()).collect::<Option<Vec<_>>>() else {
+                continue;
+            };
+            let from = product(types.iter().map(|(from, _)| *from).collect());
+            let to = product(types.iter().map(|(_, to)| *to).collect());
+            if let (Some(from), Some(to)) = (from, to) {
+                morphisms.insert(name, (from, to));
+            }
+        }
+        for (name, first, second) in self.get_swaps() {
+            if let (Some(from), Some(to)) = (product(vec![first, second]), product(vec![second, first])) {
+                morphisms.insert(name, (from, to));
+            }
+        }
+        for (name, object) in self.get_dups() {
+            if let Some(to) = product(vec![object, object]) {
+                morphisms.insert(name, (object, to));
+            }
+        }
+        for (name, from, index) in self.get_projections() {
+            let component = products
+                .iter()
+                .find(|(product, _)| *product == from)
+                .and_then(|(_, components)| components.get(index.checked_sub(1)?));
+            if let Some(to) = component {
+                morphisms.insert(name, (from, to));
+            }
+        }
+        let coproducts = self.get_coproducts();
+        for (name, to, right) in self.get_injections() {
+            let component = coproducts
+                .iter()
+                .find(|(coproduct, components)| *coproduct == to && components.len() == 2)
+                .map(|(_, components)| &components[usize::from(right)]);
+            if let Some(from) = component {
+                morphisms.insert(name, (from, to));
+            }
+        }
+        morphisms
+    }
+
+    /// Get all named wire declarations
+    pub fn get_wires(&self) -> Vec<&String> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Wire { name, .. } = stmt {
+                    Some(name)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all commutativity assertions, each side flattened to its chain
+    /// of morphisms, outermost first
+    pub fn get_commute_assertions(&self) -> Vec<(Vec<String>, Vec<String>)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::AssertCommute { lhs, rhs } = stmt {
+                    Some((lhs.chain(), rhs.chain()))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all equality assertions as (lhs, rhs)
+    pub fn get_equal_assertions(&self) -> Vec<(&String, &String)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::AssertEqual { lhs, rhs } = stmt {
+                    Some((lhs, rhs))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all mono and epi assertions as (morphism, kind)
+    pub fn get_kind_assertions(&self) -> Vec<(&String, MorphismKind)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::AssertKind { morphism, kind } = stmt {
+                    Some((morphism, *kind))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all isos as (name, inverse)
+    pub fn get_isos(&self) -> Vec<(&String, &String)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Iso { name, inverse, .. } => Some((name, inverse)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get all declared identities as (name, object)
+    pub fn get_identities(&self) -> Vec<(&String, &String)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Identity { name, object } = stmt {
+                    Some((name, object))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all test vectors as (morphism, input, expected output)
+    pub fn get_tests(&self) -> Vec<(&String, &String, &String)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Test { morphism, input, expected } = stmt {
+                    Some((morphism, input, expected))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all interface declarations
+    pub fn get_interfaces(&self) -> HashMap<&String, &Vec<(String, usize)>> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Interface { name, fields } = stmt {
+                    Some((name, fields))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get all parameter declarations with their default values
+    pub fn get_params(&self) -> Vec<(&String, usize)> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                if let Statement::Param { name, value } = stmt {
+                    Some((name, *value))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Get the interface fields of an object typed as an interface: object req : Bus
+    pub fn object_fields(&self, object: &str) -> Option<&Vec<(String, usize)>> {
+        let interface = self.statements.iter().find_map(|stmt| match stmt {
+            Statement::Object { name, width: Some(WidthExpr::Ref(interface)), .. } if name == object => {
+                Some(interface)
+            }
+            _ => None,
+        })?;
+        self.get_interfaces().get(interface).copied()
+    }
+
+    /// Resolve every object's width annotation to a bit count
+    pub fn resolve_widths(&self) -> Result<HashMap<String, usize>, String> {
+        let annotations = self.width_annotations();
+        let mut widths = self.resolve_all_widths(&annotations)?;
+        widths.retain(|name, _| annotations.contains_key(name.as_str()));
+        Ok(widths)
+    }
+
+    /// Verilog expression of every object width that depends on a `param`,
+    /// keeping parameter names symbolic and folding everything else. Empty
+    /// when the widths don't resolve
+    pub fn parametric_widths(&self) -> HashMap<String, String> {
+        let annotations = self.width_annotations();
+        let Ok(widths) = self.resolve_all_widths(&annotations) else {
+            return HashMap::new();
+        };
+        let params: Vec<&String> = self.get_params().into_iter().map(|(name, _)| name).collect();
+        annotations
+            .iter()
+            .filter_map(|(name, width)| {
+                let (verilog, _, parametric) = symbolic_width(width.as_deref()?, &annotations, &params, &widths);
+                parametric.then(|| (name.to_string(), verilog))
+            })
+            .collect()
+    }
+
+    fn width_annotations(&self) -> Annotations<'_> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| {
+                match stmt {
+                    Statement::Object { name, width, .. } | Statement::Wire { name, width } => {
+                        Some((name.as_str(), width.as_ref().map(Cow::Borrowed)))
+                    }
+                    // A product is as wide as its components side by side
+                    Statement::Product { name, components } => {
+                        let width = components
+                            .iter()
+                            .map(|component| WidthExpr::Ref(component.clone()))
+                            .reduce(|lhs, rhs| WidthExpr::Add(Box::new(lhs), Box::new(rhs)));
+                        Some((name.as_str(), width.map(Cow::Owned)))
+                    }
+                    // A coproduct holds its widest component plus the tag
+                    Statement::Coproduct { name, components } => {
+                        let width = components
+                            .iter()
+                            .map(|component| WidthExpr::Ref(component.clone()))
+                            .reduce(|lhs, rhs| WidthExpr::Max(Box::new(lhs), Box::new(rhs)))
+                            .map(|data| WidthExpr::Add(Box::new(data), Box::new(WidthExpr::Const(tag_width(components.len())))));
+                        Some((name.as_str(), width.map(Cow::Owned)))
+                    }
+                    // An enum takes as many bits as a tag telling its members apart
+                    Statement::Enum { name, members } => {
+                        Some((name.as_str(), Some(Cow::Owned(WidthExpr::Const(tag_width(members.len()))))))
+                    }
+                    // A memory is carried as its write request: enable, address and data
+                    Statement::Memory { name, width, depth } => {
+                        let request = WidthExpr::Add(Box::new(WidthExpr::Const(1 + tag_width(*depth))), Box::new(width.clone()));
+                        Some((name.as_str(), Some(Cow::Owned(request))))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Widths of objects, interfaces and parameters
+    fn resolve_all_widths(&self, annotations: &Annotations) -> Result<HashMap<String, usize>, String> {
+        // An interface used as a width stands for the whole flattened bundle
+        let interfaces = self.get_interfaces();
+        let params = self.get_params();
+        let mut widths: HashMap<String, usize> = HashMap::new();
+        #[cfg(feature = "std")]
+        widths.reserve(annotations.len() + interfaces.len() + params.len());
+        widths.extend(
+            interfaces
+                .iter()
+                .map(|(name, fields)| (name.to_string(), fields.iter().map(|(_, w)| w).sum())),
+        );
+        for (name, value) in params {
+            if annotations.contains_key(name.as_str()) || widths.contains_key(name) {
+                return Err(format!("Parameter {} has the same name as an object or interface", name));
+            }
+            widths.insert(name.clone(), value);
+        }
+        let mut visiting = Vec::new();
+        for name in annotations.keys() {
+            resolve_object_width(name, annotations, &mut widths, &mut visiting)?;
+        }
+        Ok(widths)
+    }
+}
+
+/// Width annotation of every object and wire, by name
+type Annotations<'a> = HashMap<&'a str, Option<Cow<'a, WidthExpr>>>;
+
+/// Render a width in Verilog, returning whether it is a sum (and needs
+/// parentheses under `*`) and whether it depends on a parameter
+fn symbolic_width(
+    expr: &WidthExpr,
+    annotations: &Annotations,
+    params: &[&String],
+    widths: &HashMap<String, usize>,
+) -> (String, bool, bool) {
+    let folded = || {
+        let value = expr.evaluate(widths).expect("widths are resolved");
+        (value.to_string(), false, false)
+    };
+    match expr {
+        WidthExpr::Const(_) => folded(),
+        WidthExpr::Ref(name) if params.contains(&name) => (verilog_identifier(name), false, true),
+        WidthExpr::Ref(name) => match annotations.get(name.as_str()) {
+            Some(Some(width)) => match symbolic_width(width, annotations, params, widths) {
+                symbolic @ (_, _, true) => symbolic,
+                _ => folded(),
+            },
+            _ => folded(),
+        },
+        WidthExpr::Add(lhs, rhs) | WidthExpr::Mul(lhs, rhs) | WidthExpr::Max(lhs, rhs) => {
+            let lhs = symbolic_width(lhs, annotations, params, widths);
+            let rhs = symbolic_width(rhs, annotations, params, widths);
+            if !lhs.2 && !rhs.2 {
+                return folded();
+            }
+            match expr {
+                WidthExpr::Add(..) => return (format!("{} + {}", lhs.0, rhs.0), true, true),
+                WidthExpr::Max(..) => return (format!("({0} > {1} ? {0} : {1})", lhs.0, rhs.0), false, true),
+                _ => {}
+            }
+            let factor = |(verilog, sum, _): (String, bool, bool)| if sum { format!("({})", verilog) } else { verilog };
+            (format!("{} * {}", factor(lhs), factor(rhs)), false, true)
+        }
+    }
+}
+
+fn resolve_object_width(
+    name: &str,
+    annotations: &Annotations,
+    widths: &mut HashMap<String, usize>,
+    visiting: &mut Vec<String>,
+) -> Result<usize, String> {
+    if let Some(width) = widths.get(name) {
+        return Ok(*width);
+    }
+    if visiting.iter().any(|n| n == name) {
+        visiting.push(name.to_string());
+        return Err(format!("Circular width annotation: {}", visiting.join(" -> ")));
+    }
+    let annotation = annotations
+        .get(name)
+        .ok_or_else(|| format!("Width annotation references unknown object {}", name))?;
+
+    visiting.push(name.to_string());
+    let width = match annotation {
+        Some(expr) => eval_width(expr, annotations, widths, visiting)?,
+        None => DEFAULT_WIDTH,
+    };
+    visiting.pop();
+
+    widths.insert(name.to_string(), width);
+    Ok(width)
+}
+
+fn eval_width(
+    expr: &WidthExpr,
+    annotations: &Annotations,
+    widths: &mut HashMap<String, usize>,
+    visiting: &mut Vec<String>,
+) -> Result<usize, String> {
+    match expr {
+        WidthExpr::Const(n) => Ok(*n),
+        WidthExpr::Ref(name) => resolve_object_width(name, annotations, widths, visiting),
+        WidthExpr::Add(lhs, rhs) => Ok(
+            eval_width(lhs, annotations, widths, visiting)?
+                + eval_width(rhs, annotations, widths, visiting)?
+        ),
+        WidthExpr::Mul(lhs, rhs) => Ok(
+            eval_width(lhs, annotations, widths, visiting)?
+                * eval_width(rhs, annotations, widths, visiting)?
+        ),
+        WidthExpr::Max(lhs, rhs) => Ok(
+            eval_width(lhs, annotations, widths, visiting)?
+                .max(eval_width(rhs, annotations, widths, visiting)?)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(name: &str, width: Option<WidthExpr>) -> Statement {
+        Statement::Object { name: name.to_string(), width, signed: false, initial: None, active_low: false, fraction: None, domain: None }
+    }
+
+    #[test]
+    fn test_resolve_width_arithmetic() {
+        let ast = CategoryAST {
+            statements: vec![
+                object("Sum", Some(WidthExpr::Add(
+                    Box::new(WidthExpr::Ref("A".to_string())),
+                    Box::new(WidthExpr::Const(1)),
+                ))),
+                object("A", Some(WidthExpr::Const(8))),
+                object("B", None),
+            ],
+        };
+        let widths = ast.resolve_widths().unwrap();
+        assert_eq!(widths["Sum"], 9);
+        assert_eq!(widths["A"], 8);
+        assert_eq!(widths["B"], DEFAULT_WIDTH);
+    }
+
+    #[test]
+    fn test_parametric_widths() {
+        let param = |name: &str| Statement::Param { name: name.to_string(), value: 8 };
+        let r = |name: &str| Box::new(WidthExpr::Ref(name.to_string()));
+        let ast = CategoryAST {
+            statements: vec![
+                param("W"),
+                object("A", Some(WidthExpr::Ref("W".to_string()))),
+                object("B", Some(WidthExpr::Add(r("A"), Box::new(WidthExpr::Const(1))))),
+                object("C", Some(WidthExpr::Mul(r("B"), Box::new(WidthExpr::Add(r("D"), r("D")))))),
+                object("D", Some(WidthExpr::Const(2))),
+            ],
+        };
+        assert_eq!(ast.resolve_widths().unwrap()["C"], 36);
+        let widths = ast.parametric_widths();
+        assert_eq!(widths["A"], "W");
+        assert_eq!(widths["B"], "W + 1");
+        assert_eq!(widths["C"], "(W + 1) * 4");
+        assert!(!widths.contains_key("D"));
+
+        let clash = CategoryAST { statements: vec![param("A"), object("A", None)] };
+        assert!(clash.resolve_widths().unwrap_err().contains("Parameter A"));
+    }
+
+    #[test]
+    fn test_resolve_width_errors() {
+        let unresolved = CategoryAST {
+            statements: vec![object("A", Some(WidthExpr::Ref("Missing".to_string())))],
+        };
+        assert!(unresolved.resolve_widths().unwrap_err().contains("unknown object Missing"));
+
+        let circular = CategoryAST {
+            statements: vec![
+                object("A", Some(WidthExpr::Ref("B".to_string()))),
+                object("B", Some(WidthExpr::Mul(
+                    Box::new(WidthExpr::Ref("A".to_string())),
+                    Box::new(WidthExpr::Const(2)),
+                ))),
+            ],
+        };
+        assert!(circular.resolve_widths().unwrap_err().contains("Circular"));
+    }
+
+    #[test]
+    fn test_infer_width() {
+        let widths = |name: &str| match name {
+            "in_A" | "in_B" => Some(8),
+            _ => None,
+        };
+        let add = Expr::Binary {
+            op: BinOp::Add,
+            lhs: Box::new(Expr::Ident("in_A".to_string())),
+            rhs: Box::new(Expr::Ident("in_B".to_string())),
+        };
+        assert_eq!(add.infer_width(&widths), Some(9));
+        let mul = Expr::Binary {
+            op: BinOp::Mul,
+            lhs: Box::new(add),
+            rhs: Box::new(Expr::Literal(5)),
+        };
+        assert_eq!(mul.infer_width(&widths), Some(12));
+        assert_eq!(Expr::Ident("x".to_string()).infer_width(&widths), None);
+    }
+
+    #[test]
+    fn test_signed_right_shifts() {
+        let lower = |source: &str| crate::parser::expr(source).unwrap().1.to_signed_verilog("in_A");
+        assert_eq!(lower("in >> 2"), "in_A >>> 2");
+        assert_eq!(lower("(in + 1) >> 1"), "in_A + 1 >>> 1");
+        assert_eq!(lower("~in >> 1"), "~in_A >>> 1");
+        assert_eq!(crate::parser::expr("in >> 2").unwrap().1.to_verilog("in_A"), "in_A >> 2");
+        // Verilog takes part-selects, concatenations and comparisons as
+        // unsigned, so shifting them never extends a sign bit
+        assert_eq!(lower("in[7:4] >> 1"), "in_A[7:4] >> 1");
+        assert_eq!(lower("{in[3:0], in[7:4]} >> 1"), "{in_A[3:0], in_A[7:4]} >> 1");
+        assert_eq!(lower("(in[7:4] + 1) >> 1"), "in_A[7:4] + 1 >> 1");
+        assert_eq!(lower("(in < 1) >> 1"), "(in_A < 1) >> 1");
+    }
+
+    #[test]
+    fn test_identifier_quoting_and_sanitizing() {
+        assert_eq!(source_identifier("A_1"), "A_1");
+        assert_eq!(source_identifier("weird name"), "\"weird name\"");
+        assert_eq!(verilog_identifier("weird name"), "weird_name");
+        assert_eq!(verilog_identifier("1st-stage"), "_1st_stage");
+    }
+
+    #[test]
+    fn test_interface_typed_object_width() {
+        let ast = CategoryAST {
+            statements: vec![
+                Statement::Interface {
+                    name: "Bus".to_string(),
+                    fields: vec![("data".to_string(), 32), ("valid".to_string(), 1)],
+                },
+                object("req", Some(WidthExpr::Ref("Bus".to_string()))),
+            ],
+        };
+        assert_eq!(ast.resolve_widths().unwrap()["req"], 33);
+        assert_eq!(ast.object_fields("req").unwrap().len(), 2);
+        assert!(!ast.resolve_widths().unwrap().contains_key("Bus"));
+    }
+
+    #[test]
+    fn test_parse_and_resolve_without_std() {
+        // `make test` also runs this with `--no-default-features`, where the
+        // maps are ordered and only `alloc` is available
+        let (_, ast) = crate::parser::parse_category_file("param W = 4\nobject A : W\nobject B : A + 1\nobject C = A × B\nmorphism f: A -> B = in + 1").unwrap();
+        let widths = ast.resolve_widths().unwrap();
+        assert_eq!((widths["A"], widths["B"], widths["C"]), (4, 5, 9));
+        assert_eq!(ast.get_morphisms()[&"f".to_string()], (&"A".to_string(), &"B".to_string()));
+        assert_eq!(ast.get_objects().len(), 3);
+    }
+}
@@ -0,0 +1,444 @@
+//! Random generators of valid ASTs for fuzzing the printer and parser
+//! against each other. Enabled by the `arbitrary` feature.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use crate::ast::{Attribute, BinOp, CategoryAST, Composition, Expr, MorphismKind, Statement, WidthExpr};
+use crate::parser::parse_category_file;
+
+/// Maximum nesting depth of generated expressions
+const MAX_DEPTH: u32 = 3;
+
+const IDENT_START: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_";
+const IDENT_REST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_0123456789";
+
+/// Generate an identifier the parser accepts
+pub fn arbitrary_identifier(u: &mut Unstructured) -> Result<String> {
+    let mut name = String::new();
+    name.push(*u.choose(IDENT_START)? as char);
+    for _ in 0..u.int_in_range(0..=7)? {
+        name.push(*u.choose(IDENT_REST)? as char);
+    }
+    Ok(name)
+}
+
+/// Generate a Verilog number literal, sized and based or plain decimal
+pub fn arbitrary_number(u: &mut Unstructured) -> Result<String> {
+    let value: u16 = u.arbitrary()?;
+    Ok(if u.arbitrary()? {
+        format!("16'h{:X}", value)
+    } else {
+        value.to_string()
+    })
+}
+
+/// Generate a row of a truth table, `count` digits long
+fn arbitrary_bits(u: &mut Unstructured, digits: &[u8], count: usize) -> Result<String> {
+    (0..count).map(|_| Ok(*u.choose(digits)? as char)).collect()
+}
+
+/// Generate a name, sometimes one that has to be quoted
+pub fn arbitrary_name(u: &mut Unstructured) -> Result<String> {
+    if !u.ratio(1, 4)? {
+        return arbitrary_identifier(u);
+    }
+    let mut name = arbitrary_identifier(u)?;
+    for _ in 0..u.int_in_range(1..=3)? {
+        name.push(*u.choose(b" -.")? as char);
+        name.push_str(&arbitrary_identifier(u)?);
+    }
+    Ok(name)
+}
+
+fn arbitrary_width_expr(u: &mut Unstructured, depth: u32) -> Result<WidthExpr> {
+    let choice = if depth >= MAX_DEPTH { u.int_in_range(0..=1)? } else { u.int_in_range(0..=6)? };
+    Ok(match choice {
+        0 => WidthExpr::Const(u.int_in_range(1..=64)?),
+        1 => WidthExpr::Ref(arbitrary_name(u)?),
+        2 => WidthExpr::Add(
+            Box::new(arbitrary_width_expr(u, depth + 1)?),
+            Box::new(arbitrary_width_expr(u, depth + 1)?),
+        ),
+        3 => WidthExpr::Mul(
+            Box::new(arbitrary_width_expr(u, depth + 1)?),
+            Box::new(arbitrary_width_expr(u, depth + 1)?),
+        ),
+        _ => WidthExpr::Max(
+            Box::new(arbitrary_width_expr(u, depth + 1)?),
+            Box::new(arbitrary_width_expr(u, depth + 1)?),
+        ),
+    })
+}
+
+fn arbitrary_expr(u: &mut Unstructured, depth: u32) -> Result<Expr> {
+    let choice = if depth >= MAX_DEPTH { u.int_in_range(0..=2)? } else { u.int_in_range(0..=4)? };
+    Ok(match choice {
+        0 => Expr::Literal(u.arbitrary()?),
+        1 => Expr::Ident(arbitrary_name(u)?),
+        2 => {
+            let lsb = u.int_in_range(0..=31)?;
+            Expr::Slice { name: arbitrary_name(u)?, msb: lsb + u.int_in_range(0..=31)?, lsb }
+        }
+        3 => Expr::Concat((0..u.int_in_range(1..=3)?).map(|_| arbitrary_expr(u, depth + 1)).collect::<Result<_>>()?),
+        4 => Expr::Not(Box::new(arbitrary_expr(u, depth + 1)?)),
+        5 => Expr::If {
+            condition: Box::new(arbitrary_expr(u, depth + 1)?),
+            then: Box::new(arbitrary_expr(u, depth + 1)?),
+            otherwise: Box::new(arbitrary_expr(u, depth + 1)?),
+        },
+        _ => Expr::Binary {
+            op: u.arbitrary()?,
+            lhs: Box::new(arbitrary_expr(u, depth + 1)?),
+            rhs: Box::new(arbitrary_expr(u, depth + 1)?),
+        },
+    })
+}
+
+fn arbitrary_chain(u: &mut Unstructured) -> Result<Vec<String>> {
+    (0..u.int_in_range(1..=4)?).map(|_| arbitrary_name(u)).collect()
+}
+
+fn arbitrary_composition(u: &mut Unstructured, depth: u32) -> Result<Composition> {
+    if depth >= MAX_DEPTH || u.arbitrary()? {
+        return Ok(Composition::Morphism(arbitrary_name(u)?));
+    }
+    Ok(Composition::Compose(
+        Box::new(arbitrary_composition(u, depth + 1)?),
+        Box::new(arbitrary_composition(u, depth + 1)?),
+    ))
+}
+
+impl<'a> Arbitrary<'a> for WidthExpr {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_width_expr(u, 0)
+    }
+}
+
+impl<'a> Arbitrary<'a> for BinOp {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(*u.choose(&[
+            BinOp::Add,
+            BinOp::Sub,
+            BinOp::Mul,
+            BinOp::Shl,
+            BinOp::Shr,
+            BinOp::And,
+            BinOp::Or,
+            BinOp::Xor,
+            BinOp::Eq,
+            BinOp::Ne,
+            BinOp::Lt,
+            BinOp::Le,
+            BinOp::Gt,
+            BinOp::Ge,
+        ])?)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Expr {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_expr(u, 0)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Statement {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=38)? {
+            0 => {
+                let width: Option<WidthExpr> = u.arbitrary()?;
+                // Only a constant width splits into integer and fraction bits
+                let fraction = match width {
+                    Some(WidthExpr::Const(width)) if u.ratio(1, 4)? => Some(u.int_in_range(0..=width)?),
+                    _ => None,
+                };
+                Statement::Object {
+                    name: arbitrary_name(u)?,
+                    width,
+                    signed: u.arbitrary()?,
+                    initial: if u.ratio(1, 4)? { Some(arbitrary_number(u)?) } else { None },
+                    active_low: u.arbitrary()?,
+                    fraction,
+                    domain: if u.ratio(1, 4)? { Some(arbitrary_name(u)?) } else { None },
+                }
+            }
+            1 => {
+                let body: Option<Expr> = u.arbitrary()?;
+                // The endpoints of a generic morphism take its parameter as their width
+                let generic = if u.ratio(1, 4)? { Some(arbitrary_identifier(u)?) } else { None };
+                let generic_width = |u: &mut Unstructured<'a>| -> Result<Option<WidthExpr>> {
+                    if generic.is_some() { Ok(None) } else { u.arbitrary() }
+                };
+                Statement::Morphism {
+                    name: arbitrary_name(u)?,
+                    from: arbitrary_name(u)?,
+                    to: arbitrary_name(u)?,
+                    from_width: generic_width(u)?,
+                    to_width: generic_width(u)?,
+                    lanes: if u.arbitrary()? { Some(u.int_in_range(1..=16)?) } else { None },
+                    registered: u.arbitrary()?,
+                    guard: if u.ratio(1, 4)? { Some(arbitrary_identifier(u)?) } else { None },
+                    // Only a morphism without a body can be a black box
+                    external: if body.is_none() && u.ratio(1, 4)? { Some(format!("{}.v", arbitrary_identifier(u)?)) } else { None },
+                    handshake: u.arbitrary()?,
+                    maybe: u.arbitrary()?,
+                    // Only a body can read bindings
+                    bindings: match body {
+                        Some(_) => (0..u.int_in_range(0..=2)?).map(|_| Ok((arbitrary_identifier(u)?, u.arbitrary()?))).collect::<Result<_>>()?,
+                        None => Vec::new(),
+                    },
+                    body,
+                    generic,
+                    attributes: (0..u.int_in_range(0..=2)?)
+                        .map(|_| {
+                            Ok(Attribute {
+                                name: arbitrary_identifier(u)?,
+                                args: (0..u.int_in_range(0..=2)?)
+                                    .map(|_| if u.arbitrary()? { arbitrary_identifier(u) } else { Ok(u.arbitrary::<u16>()?.to_string()) })
+                                    .collect::<Result<_>>()?,
+                            })
+                        })
+                        .collect::<Result<_>>()?,
+                }
+            }
+            2 => Statement::Interface {
+                name: arbitrary_name(u)?,
+                fields: (0..u.int_in_range(1..=4)?)
+                    .map(|_| Ok((arbitrary_name(u)?, u.int_in_range(1..=64)?)))
+                    .collect::<Result<_>>()?,
+            },
+            3 => Statement::Wire {
+                name: arbitrary_name(u)?,
+                width: u.arbitrary()?,
+            },
+            5 => {
+                let name = arbitrary_name(u)?;
+                let bodies: Option<(Expr, Expr)> = u.arbitrary()?;
+                Statement::Iso {
+                    inverse: format!("{}_inv", name),
+                    name,
+                    from: arbitrary_name(u)?,
+                    to: arbitrary_name(u)?,
+                    body: bodies.as_ref().map(|(body, _)| body.clone()),
+                    inverse_body: bodies.map(|(_, inverse)| inverse),
+                }
+            }
+            4 => Statement::Param {
+                name: arbitrary_name(u)?,
+                value: u.int_in_range(1..=64)?,
+            },
+            6 => {
+                let mut chain = vec![arbitrary_name(u)?];
+                chain.extend(arbitrary_chain(u)?);
+                Statement::Define {
+                    name: arbitrary_name(u)?,
+                    junction_widths: (1..chain.len())
+                        .map(|_| Ok(if u.arbitrary()? { Some(u.int_in_range(1..=64)?) } else { None }))
+                        .collect::<Result<_>>()?,
+                    chain,
+                }
+            }
+            7 => Statement::Test {
+                morphism: arbitrary_name(u)?,
+                input: arbitrary_number(u)?,
+                expected: arbitrary_number(u)?,
+            },
+            8 => Statement::Identity {
+                name: arbitrary_name(u)?,
+                object: arbitrary_name(u)?,
+            },
+            9 => Statement::Product {
+                name: arbitrary_name(u)?,
+                components: (0..u.int_in_range(2..=4)?).map(|_| arbitrary_name(u)).collect::<Result<_>>()?,
+            },
+            10 => Statement::Coproduct {
+                name: arbitrary_name(u)?,
+                components: (0..u.int_in_range(2..=4)?).map(|_| arbitrary_name(u)).collect::<Result<_>>()?,
+            },
+            11 => Statement::Case {
+                name: arbitrary_name(u)?,
+                from: arbitrary_name(u)?,
+                to: arbitrary_name(u)?,
+                branches: (0..u.int_in_range(1..=4)?).map(|_| arbitrary_name(u)).collect::<Result<_>>()?,
+            },
+            13 => Statement::Functor {
+                name: arbitrary_name(u)?,
+                from: arbitrary_name(u)?,
+                to: arbitrary_name(u)?,
+                mapping: (0..u.int_in_range(0..=4)?)
+                    .map(|_| Ok((arbitrary_name(u)?, arbitrary_name(u)?)))
+                    .collect::<Result<_>>()?,
+            },
+            14 => Statement::Nat {
+                name: arbitrary_name(u)?,
+                from: arbitrary_name(u)?,
+                to: arbitrary_name(u)?,
+                components: (0..u.int_in_range(0..=3)?)
+                    .map(|_| Ok((arbitrary_name(u)?, u.arbitrary()?)))
+                    .collect::<Result<_>>()?,
+            },
+            12 => Statement::Tensor {
+                name: arbitrary_name(u)?,
+                factors: (0..u.int_in_range(2..=4)?).map(|_| arbitrary_name(u)).collect::<Result<_>>()?,
+            },
+            17 => Statement::MultiInput {
+                name: arbitrary_name(u)?,
+                from: (0..u.int_in_range(2..=4)?).map(|_| arbitrary_name(u)).collect::<Result<_>>()?,
+                to: arbitrary_name(u)?,
+                body: u.arbitrary()?,
+                registered: u.arbitrary()?,
+            },
+            16 => Statement::Constant {
+                name: arbitrary_name(u)?,
+                to: arbitrary_name(u)?,
+                value: arbitrary_number(u)?,
+            },
+            19 => Statement::Clock { name: arbitrary_name(u)? },
+            21 => Statement::Category { name: arbitrary_name(u)? },
+            22 => {
+                let width: Option<WidthExpr> = u.arbitrary()?;
+                let fraction = match width {
+                    Some(WidthExpr::Const(width)) if u.ratio(1, 4)? => Some(u.int_in_range(0..=width)?),
+                    _ => None,
+                };
+                let start = u.int_in_range(0..=8)?;
+                Statement::ObjectFamily {
+                    name: arbitrary_identifier(u)?,
+                    start,
+                    end: start + u.int_in_range(0..=8)?,
+                    width,
+                    signed: u.arbitrary()?,
+                    fraction,
+                }
+            }
+            23 => {
+                // Every row of a table is as wide as the first
+                let (inputs, outputs) = (u.int_in_range(1..=8)?, u.int_in_range(1..=8)?);
+                Statement::Table {
+                    name: arbitrary_name(u)?,
+                    from: arbitrary_name(u)?,
+                    to: arbitrary_name(u)?,
+                    rows: (0..u.int_in_range(1..=4)?)
+                        .map(|_| Ok((arbitrary_bits(u, b"01?", inputs)?, arbitrary_bits(u, b"01", outputs)?)))
+                        .collect::<Result<_>>()?,
+                }
+            }
+            24 => Statement::Verilog {
+                name: arbitrary_name(u)?,
+                from: arbitrary_name(u)?,
+                to: arbitrary_name(u)?,
+                text: u.choose(&["assign out = ^in;", "assign out = {in[0], in[7:1]};", "wire t = &in;\nassign out = {8{t}};"])?.to_string(),
+            },
+            25 => Statement::Library {
+                name: arbitrary_name(u)?,
+                element: arbitrary_identifier(u)?,
+                from: (0..u.int_in_range(1..=3)?).map(|_| arbitrary_name(u)).collect::<Result<_>>()?,
+                to: arbitrary_name(u)?,
+            },
+            26 => Statement::Top { name: arbitrary_identifier(u)? },
+            27 => Statement::Pragma {
+                target: arbitrary_name(u)?,
+                text: u.choose(&["(* keep *)", "(* keep_hierarchy = \"yes\" *)", "// synthesis translate_off \\", ""])?.to_string(),
+            },
+            28 => Statement::Swap { name: arbitrary_name(u)?, first: arbitrary_name(u)?, second: arbitrary_name(u)? },
+            29 => Statement::Dup { name: arbitrary_name(u)?, object: arbitrary_name(u)? },
+            30 => Statement::Projection { name: arbitrary_name(u)?, product: arbitrary_name(u)?, index: u.int_in_range(1..=4)? },
+            31 => Statement::Injection { name: arbitrary_name(u)?, coproduct: arbitrary_name(u)?, right: u.arbitrary()? },
+            32 => Statement::AssertEqual { lhs: arbitrary_name(u)?, rhs: arbitrary_name(u)? },
+            33 => Statement::AssertKind {
+                morphism: arbitrary_name(u)?,
+                kind: *u.choose(&[MorphismKind::Mono, MorphismKind::Epi])?,
+            },
+            34 => Statement::Enum {
+                name: arbitrary_name(u)?,
+                members: (0..u.int_in_range(1..=4)?).map(|_| arbitrary_identifier(u)).collect::<Result<_>>()?,
+            },
+            35 => Statement::Memory {
+                name: arbitrary_name(u)?,
+                width: arbitrary_width_expr(u, 0)?,
+                depth: u.int_in_range(1..=1024)?,
+            },
+            36 => Statement::Rom {
+                name: arbitrary_name(u)?,
+                from: arbitrary_name(u)?,
+                to: arbitrary_name(u)?,
+                path: format!("{}.hex", arbitrary_identifier(u)?),
+            },
+            37 => Statement::Template {
+                name: arbitrary_identifier(u)?,
+                params: (0..u.int_in_range(0..=3)?).map(|_| arbitrary_identifier(u)).collect::<Result<_>>()?,
+                body: u.choose(&["object R : X", "for i in 1..n { morphism s[i]: X -> X = in + 1 }", "section s {\n  object A : 8\n}"])?.to_string(),
+            },
+            38 => Statement::Use {
+                template: arbitrary_name(u)?,
+                args: (0..u.int_in_range(0..=3)?)
+                    .map(|_| if u.arbitrary()? { arbitrary_name(u) } else { Ok(u.int_in_range(0..=64u32)?.to_string()) })
+                    .collect::<Result<_>>()?,
+                alias: if u.arbitrary()? { Some(arbitrary_identifier(u)?) } else { None },
+            },
+            20 => Statement::Trace { name: arbitrary_name(u)?, step: arbitrary_name(u)? },
+            15 => Statement::Import {
+                path: format!("{}.cat", arbitrary_identifier(u)?),
+                alias: if u.arbitrary()? { Some(arbitrary_identifier(u)?) } else { None },
+            },
+            _ => Statement::AssertCommute {
+                lhs: arbitrary_composition(u, 0)?,
+                rhs: arbitrary_composition(u, 0)?,
+            },
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for CategoryAST {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        // The file grammar requires at least one statement
+        let mut statements = vec![u.arbitrary()?];
+        for stmt in u.arbitrary_iter()? {
+            statements.push(stmt?);
+        }
+        Ok(CategoryAST { statements })
+    }
+}
+
+/// Print an AST with `to_source`, parse it back and report any divergence
+pub fn check_round_trip(ast: &CategoryAST) -> std::result::Result<(), String> {
+    let source = ast.to_source();
+    let (rest, parsed) = parse_category_file(&source)
+        .map_err(|e| format!("printed source does not parse: {}\n{}", e, source))?;
+    if !rest.is_empty() {
+        return Err(format!("parser stopped early at `{}`\n{}", rest, source));
+    }
+    if &parsed != ast {
+        return Err(format!("round trip changed the AST:\n{}\n{:?}\n{:?}", source, ast, parsed));
+    }
+    Ok(())
+}
+
+/// Fuzz entry point: build an AST from raw bytes and check its round trip
+pub fn fuzz_round_trip(data: &[u8]) -> std::result::Result<(), String> {
+    match CategoryAST::arbitrary(&mut Unstructured::new(data)) {
+        Ok(ast) => check_round_trip(&ast),
+        // Not enough bytes to build an AST; nothing to check
+        Err(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_generated_asts() {
+        // xorshift keeps the inputs deterministic without a rand dependency
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..500 {
+            let bytes: Vec<u8> = (0..512)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state as u8
+                })
+                .collect();
+            fuzz_round_trip(&bytes).unwrap();
+        }
+    }
+}
@@ -0,0 +1,20 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod ast;
+#[cfg(feature = "std")]
+pub mod dag;
+#[cfg(all(feature = "std", any(test, feature = "arbitrary")))]
+pub mod fuzz;
+#[cfg(feature = "std")]
+pub mod lint;
+#[cfg(feature = "std")]
+pub mod loader;
+#[cfg(feature = "std")]
+pub mod optimize;
+pub mod parser;
+#[cfg(feature = "std")]
+pub mod report;
+#[cfg(all(feature = "std", any(test, feature = "yaml")))]
+pub mod yaml;
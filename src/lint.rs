@@ -0,0 +1,535 @@
+use petgraph::algo::kosaraju_scc;
+use petgraph::visit::{EdgeFiltered, EdgeRef};
+use petgraph::Direction;
+use crate::ast::{CategoryAST, Statement, TERMINAL_OBJECT};
+use crate::dag::{input_port, CategoryDAG, DAGNode};
+
+/// Reserved words of Verilog-2005 that cannot be used as signal or module names
+const VERILOG_KEYWORDS: &[&str] = &[
+    "always", "and", "assign", "automatic", "begin", "buf", "bufif0", "bufif1",
+    "case", "casex", "casez", "cell", "cmos", "config", "deassign", "default",
+    "defparam", "design", "disable", "edge", "else", "end", "endcase",
+    "endconfig", "endfunction", "endgenerate", "endmodule", "endprimitive",
+    "endspecify", "endtable", "endtask", "event", "for", "force", "forever",
+    "fork", "function", "generate", "genvar", "highz0", "highz1", "if",
+    "ifnone", "incdir", "include", "initial", "inout", "input", "instance",
+    "integer", "join", "large", "liblist", "library", "localparam",
+    "macromodule", "medium", "module", "nand", "negedge", "nmos", "nor",
+    "noshowcancelled", "not", "notif0", "notif1", "or", "output", "parameter",
+    "pmos", "posedge", "primitive", "pull0", "pull1", "pulldown", "pullup",
+    "pulsestyle_ondetect", "pulsestyle_onevent", "rcmos", "real", "realtime",
+    "reg", "release", "repeat", "rnmos", "rpmos", "rtran", "rtranif0",
+    "rtranif1", "scalared", "showcancelled", "signed", "small", "specify",
+    "specparam", "strong0", "strong1", "supply0", "supply1", "table", "task",
+    "time", "tran", "tranif0", "tranif1", "tri", "tri0", "tri1", "triand",
+    "trior", "trireg", "unsigned", "use", "uwire", "vectored", "wait", "wand",
+    "weak0", "weak1", "while", "wire", "wor", "xnor", "xor",
+];
+
+/// Severity of a lint finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Synthesizable, but likely to produce tool warnings or surprising hardware
+    Warning,
+    /// Will not elaborate or synthesize as written
+    Error,
+}
+
+/// A single lint finding with an optional suggested fix
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Short stable identifier of the lint, e.g. `verilog-keyword`
+    pub code: &'static str,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    fn warning(code: &'static str, message: String, suggestion: Option<String>) -> Self {
+        Self { severity: Severity::Warning, code, message, suggestion }
+    }
+
+    fn error(code: &'static str, message: String, suggestion: Option<String>) -> Self {
+        Self { severity: Severity::Error, code, message, suggestion }
+    }
+}
+
+/// Check whether a name collides with a Verilog reserved word
+pub fn is_verilog_keyword(name: &str) -> bool {
+    VERILOG_KEYWORDS.contains(&name)
+}
+
+/// Run every lint over the AST and its DAG
+pub fn lint(ast: &CategoryAST, dag: &CategoryDAG) -> Vec<Diagnostic> {
+    let mut diagnostics = lint_keyword_names(ast);
+    diagnostics.extend(lint_placeholder_bodies(ast));
+    diagnostics.extend(lint_width_mismatch(dag));
+    diagnostics.extend(lint_zero_width(dag));
+    diagnostics.extend(lint_mixed_signedness(dag));
+    diagnostics.extend(lint_clock_domain_crossings(dag));
+    diagnostics.extend(lint_initial_values(dag));
+    diagnostics.extend(lint_narrowing(dag));
+    diagnostics.extend(lint_feedback(dag));
+    diagnostics
+}
+
+/// Flag objects, morphisms and parameters whose names are Verilog reserved
+/// words, including those a template declares where it is used
+pub fn lint_keyword_names(ast: &CategoryAST) -> Vec<Diagnostic> {
+    let expanded = ast.expanded();
+    let ast = expanded.as_deref().unwrap_or(ast);
+    let mut diagnostics = Vec::new();
+    for stmt in &ast.statements {
+        let (kind, name) = match stmt {
+            Statement::Object { name, .. } => ("Object", name),
+            Statement::ObjectFamily { name, .. } => ("Object family", name),
+            Statement::Product { name, .. } => ("Product", name),
+            Statement::Coproduct { name, .. } => ("Coproduct", name),
+            Statement::Enum { name, .. } => ("Enum", name),
+            Statement::Memory { name, .. } => ("Memory", name),
+            Statement::Wire { name, .. } => ("Wire", name),
+            Statement::Morphism { name, .. } | Statement::MultiInput { name, .. } | Statement::Table { name, .. } | Statement::Verilog { name, .. } | Statement::Rom { name, .. } | Statement::Library { name, .. } => ("Morphism", name),
+            Statement::Iso { name, .. } => ("Iso", name),
+            Statement::Param { name, .. } => ("Parameter", name),
+            Statement::Define { name, .. } => ("Composition", name),
+            Statement::Identity { name, .. } => ("Identity", name),
+            Statement::Case { name, .. } => ("Case analysis", name),
+            Statement::Constant { name, .. } => ("Constant", name),
+            Statement::Tensor { name, .. } => ("Tensor", name),
+            Statement::Swap { name, .. } => ("Swap", name),
+            Statement::Dup { name, .. } => ("Duplication", name),
+            Statement::Projection { name, .. } => ("Projection", name),
+            Statement::Injection { name, .. } => ("Injection", name),
+            Statement::Trace { name, .. } => ("Trace", name),
+            Statement::Functor { name, .. } => ("Functor", name),
+            Statement::Nat { name, .. } => ("Natural transformation", name),
+            Statement::Clock { name } => ("Clock", name),
+            Statement::Top { name } => ("Top module", name),
+            _ => continue,
+        };
+        if is_verilog_keyword(name) {
+            diagnostics.push(Diagnostic::error(
+                "verilog-keyword",
+                format!("{} `{}` is named like a Verilog keyword", kind, name),
+                Some(format!("rename it, e.g. `{}_`", name)),
+            ));
+        }
+    }
+    diagnostics
+}
+
+/// Flag morphisms that will be lowered to placeholder logic. A bodiless
+/// morphism into a coproduct is an injection and one into the terminal
+/// object a sink, and neither needs a body
+pub fn lint_placeholder_bodies(ast: &CategoryAST) -> Vec<Diagnostic> {
+    let coproducts = ast.get_coproducts();
+    let objects = ast.get_objects();
+    let injection = |from: &String, to: &String| coproducts.iter().any(|(name, components)| *name == to && components.contains(from));
+    let sink = |to: &String| to == TERMINAL_OBJECT && !objects.contains(&to);
+    ast.statements
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Morphism { name, from, to, body: None, external: None, .. } if !injection(from, to) && !sink(to) => Some(Diagnostic::warning(
+                "placeholder-body",
+                format!(
+                    "Morphism `{}`: {} -> {} has no body; placeholder logic `in + 1` will be emitted",
+                    name, from, to
+                ),
+                Some(format!("give `{}` an explicit body", name)),
+            )),
+            Statement::MultiInput { name, from, to, body: None, .. } => Some(Diagnostic::warning(
+                "placeholder-body",
+                format!(
+                    "Morphism `{}`: ({}) -> {} has no body; placeholder logic `{} + 1` will be emitted",
+                    name, from.join(", "), to, from[0]
+                ),
+                Some(format!("give `{}` an explicit body", name)),
+            )),
+            Statement::Iso { name, from, to, body: None, .. } => Some(Diagnostic::warning(
+                "placeholder-body",
+                format!(
+                    "Iso `{}`: {} <-> {} has no bodies; placeholder logic `in + 1` will be emitted in both directions",
+                    name, from, to
+                ),
+                Some(format!("give `{}` both bodies: `= forward, inverse`", name)),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Flag morphism bodies whose inferred width doesn't fit the target object,
+/// which silently drops the upper bits on assignment
+pub fn lint_width_mismatch(dag: &CategoryDAG) -> Vec<Diagnostic> {
+    dag.graph
+        .node_weights()
+        .filter_map(|node| {
+            let DAGNode::Morphism { name, from, to, body: Some(body), .. } = node else {
+                return None;
+            };
+            let from_width = dag.object_width(from)?;
+            let to_width = dag.object_width(to)?;
+            let port = input_port(from);
+            // A multi-input morphism reads its objects by name
+            let inputs = dag.morphism_inputs(name).unwrap_or_default();
+            let inferred = body.infer_width(&|signal| match inputs.iter().any(|object| object == signal) {
+                true => dag.object_width(signal),
+                false => (signal == "in" || signal == port).then_some(from_width),
+            })?;
+            (inferred > to_width).then(|| {
+                Diagnostic::warning(
+                    "width-truncation",
+                    format!(
+                        "Morphism `{}` computes {} bits but {} is {} bits wide; the upper bits are truncated",
+                        name, inferred, to, to_width
+                    ),
+                    Some(format!("widen {} to {} bits or narrow the body", to, inferred)),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Flag zero-width data-flow edges, which become `[-1:0]` ranges
+pub fn lint_zero_width(dag: &CategoryDAG) -> Vec<Diagnostic> {
+    dag.graph
+        .edge_indices()
+        .filter(|&edge| dag.graph[edge].width == 0)
+        .filter_map(|edge| dag.graph.edge_endpoints(edge))
+        .map(|(from, to)| {
+            Diagnostic::error(
+                "zero-width",
+                format!(
+                    "Signal between `{}` and `{}` has zero width",
+                    node_name(&dag.graph[from]),
+                    node_name(&dag.graph[to])
+                ),
+                Some("declare a width of at least 1 bit".to_string()),
+            )
+        })
+        .collect()
+}
+
+/// Flag morphisms between a signed and an unsigned object, whose value is
+/// reinterpreted rather than converted
+pub fn lint_mixed_signedness(dag: &CategoryDAG) -> Vec<Diagnostic> {
+    dag.graph
+        .node_weights()
+        .filter_map(|node| {
+            let DAGNode::Morphism { name, from, to, .. } = node else {
+                return None;
+            };
+            // The unit and terminal objects carry no value to reinterpret
+            if dag.constant_value(name).is_some() || dag.sink_source(name).is_some() {
+                return None;
+            }
+            let sign = |obj: &str| if dag.object_signed(obj) { "signed" } else { "unsigned" };
+            (dag.object_signed(from) != dag.object_signed(to)).then(|| {
+                Diagnostic::warning(
+                    "mixed-signedness",
+                    format!(
+                        "Morphism `{}` maps {} {} to {} {}; the bits are reinterpreted",
+                        name, sign(from), from, sign(to), to
+                    ),
+                    Some(format!("declare {} and {} with the same signedness", from, to)),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Flag morphisms from one clock domain into another, whose value would be
+/// sampled without a synchronizer
+pub fn lint_clock_domain_crossings(dag: &CategoryDAG) -> Vec<Diagnostic> {
+    dag.graph
+        .node_weights()
+        .filter_map(|node| {
+            let DAGNode::Morphism { name, from, to, .. } = node else {
+                return None;
+            };
+            let (source, target) = (dag.object_domain(from)?, dag.object_domain(to)?);
+            (source != target).then(|| {
+                Diagnostic::warning(
+                    "clock-domain-crossing",
+                    format!(
+                        "Morphism `{}` maps {} on clock {} to {} on clock {}",
+                        name, from, source, to, target
+                    ),
+                    Some(format!("synchronize {} into clock {} before {} reads it", from, target, name)),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Flag objects narrower than both an object feeding them and an object they
+/// feed: the upper bits are dropped on the way in and never come back
+pub fn lint_narrowing(dag: &CategoryDAG) -> Vec<Diagnostic> {
+    let morphism_name = |idx| match &dag.graph[idx] {
+        DAGNode::Morphism { name, .. } => Some(name.clone()),
+        DAGNode::Object { .. } => None,
+    };
+    dag.graph
+        .node_indices()
+        .filter(|&idx| matches!(dag.graph[idx], DAGNode::Object { .. }))
+        .filter_map(|idx| {
+            let producers: Vec<String> = dag.graph.neighbors_directed(idx, Direction::Incoming).filter_map(morphism_name).collect();
+            let consumers: Vec<String> = dag.graph.neighbors_directed(idx, Direction::Outgoing).filter_map(morphism_name).collect();
+            producers.iter().flat_map(|f| consumers.iter().map(move |g| [f.clone(), g.clone()])).find_map(|chain| {
+                let [(from, from_width), (object, width), (to, to_width)] = dag.path_widths(&chain)[..] else {
+                    return None;
+                };
+                dag.path_narrowing(&chain).map(|_| {
+                    Diagnostic::warning(
+                        "narrowing-path",
+                        format!(
+                            "`{} ∘ {}` narrows {} ({} bits) to {} ({} bits) and widens again to {} ({} bits); the upper bits are lost",
+                            chain[1], chain[0], from, from_width, object, width, to, to_width
+                        ),
+                        Some(format!("widen {} or narrow {}", object, to)),
+                    )
+                })
+            })
+        })
+        .collect()
+}
+
+/// Flag initial values on objects that don't become registers; wires and
+/// inputs can't hold a start value, so the value is ignored
+pub fn lint_initial_values(dag: &CategoryDAG) -> Vec<Diagnostic> {
+    dag.graph
+        .node_indices()
+        .filter_map(|idx| {
+            let DAGNode::Object { name, initial: Some(initial), .. } = &dag.graph[idx] else {
+                return None;
+            };
+            let registered = dag.graph
+                .neighbors_directed(idx, Direction::Incoming)
+                .any(|producer| matches!(dag.graph[producer], DAGNode::Morphism { registered: true, .. }));
+            (!registered).then(|| {
+                Diagnostic::warning(
+                    "ineffective-initial",
+                    format!("Object `{}` has initial value {} but is not a register; it is ignored", name, initial),
+                    Some(format!("produce `{}` with a `seq` morphism or drop the initial value", name)),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Flag cycles in the DAG, which become combinational feedback loops. A
+/// loop through a registered morphism is broken by its register
+pub fn lint_feedback(dag: &CategoryDAG) -> Vec<Diagnostic> {
+    let combinational = EdgeFiltered::from_fn(&dag.graph, |edge| !dag.is_registered(edge.source()));
+    kosaraju_scc(&combinational)
+        .into_iter()
+        .filter(|scc| scc.len() > 1 || (dag.graph.contains_edge(scc[0], scc[0]) && !dag.is_registered(scc[0])))
+        .map(|mut scc| {
+            scc.sort();
+            let names: Vec<&str> = scc.iter().map(|&idx| node_name(&dag.graph[idx])).collect();
+            Diagnostic::error(
+                "combinational-feedback",
+                format!("Combinational feedback loop through {}", names.join(", ")),
+                Some("break the loop with a registered morphism".to_string()),
+            )
+        })
+        .collect()
+}
+
+fn node_name(node: &DAGNode) -> &str {
+    match node {
+        DAGNode::Object { name, .. } => name,
+        DAGNode::Morphism { name, .. } => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Expr;
+    use crate::dag::DAGEdge;
+    use crate::parser::parse_category_file;
+
+    fn ast(statements: Vec<Statement>) -> CategoryAST {
+        CategoryAST { statements }
+    }
+
+    fn object(name: &str) -> Statement {
+        Statement::Object { name: name.to_string(), width: None, signed: false, initial: None, active_low: false, fraction: None, domain: None }
+    }
+
+    fn morphism(name: &str, from: &str, to: &str) -> Statement {
+        Statement::Morphism {
+            name: name.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            from_width: None,
+            to_width: None,
+            body: None,
+            bindings: Vec::new(),
+            lanes: None,
+            registered: false,
+            guard: None,
+            external: None,
+            handshake: false,
+            maybe: false,
+            generic: None,
+            attributes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_lint_keyword_names() {
+        let ast = ast(vec![
+            object("wire"),
+            object("B"),
+            morphism("assign", "wire", "B"),
+        ]);
+        let diagnostics = lint_keyword_names(&ast);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(|d| d.code == "verilog-keyword"));
+        assert!(diagnostics[0].message.contains("`wire`"));
+        assert_eq!(diagnostics[1].suggestion.as_deref(), Some("rename it, e.g. `assign_`"));
+
+        // A name a template only declares once it is used
+        let (_, used) = parse_category_file("template t(n) {\n    object n\n}\nuse t(reg)").unwrap();
+        let diagnostics = lint_keyword_names(&used);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("Object `reg`"), "{}", diagnostics[0].message);
+    }
+
+    #[test]
+    fn test_lint_placeholder_bodies() {
+        let ast = ast(vec![
+            object("A"),
+            object("B"),
+            morphism("f", "A", "B"),
+            Statement::Morphism {
+                name: "g".to_string(),
+                from: "A".to_string(),
+                to: "B".to_string(),
+                from_width: None,
+                to_width: None,
+                body: Some(Expr::Ident("in".to_string())),
+                bindings: Vec::new(),
+                lanes: None,
+                registered: false,
+                guard: None,
+                external: None,
+                handshake: false,
+                maybe: false,
+                generic: None,
+                attributes: Vec::new(),
+            },
+        ]);
+        let diagnostics = lint_placeholder_bodies(&ast);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "placeholder-body");
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0].message.contains("`f`"));
+
+        let (_, isos) = parse_category_file("object A\nobject B\niso h: A <-> B\niso k: A <-> B = in, in").unwrap();
+        let diagnostics = lint_placeholder_bodies(&isos);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("Iso `h`: A <-> B"));
+
+        // A black box has no body of its own to generate
+        let (_, black_box) = parse_category_file("object A\nobject B\nmorphism ext: A -> B extern \"ext.v\"").unwrap();
+        assert!(lint_placeholder_bodies(&black_box).is_empty());
+
+        // A sink discards its input, unless T is an ordinary object
+        let (_, sink) = parse_category_file("object A\nmorphism drop: A -> T").unwrap();
+        assert!(lint_placeholder_bodies(&sink).is_empty());
+        let (_, declared) = parse_category_file("object A\nobject T\nmorphism drop: A -> T").unwrap();
+        assert_eq!(lint_placeholder_bodies(&declared).len(), 1);
+
+        // Nor does an injection into a coproduct
+        let (_, injection) = parse_category_file("object A\nobject B\nobject C = A + B\nmorphism inl: A -> C").unwrap();
+        assert!(lint_placeholder_bodies(&injection).is_empty());
+    }
+
+    #[test]
+    fn test_lint_width_truncation() {
+        let source = "object A : 8\nobject B : 8\nobject C : 9\nmorphism f: A -> B = in + 1\nmorphism g: A -> C = in + 1\n";
+        let (_, ast) = parse_category_file(source).unwrap();
+        let dag = CategoryDAG::from_ast(&ast).unwrap();
+        let diagnostics = lint_width_mismatch(&dag);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "width-truncation");
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0].message.contains("`f` computes 9 bits"));
+    }
+
+    #[test]
+    fn test_lint_mixed_signedness() {
+        let source = "object A : signed 8\nobject B : signed 8\nobject C : 8\nmorphism f: A -> B\nmorphism g: B -> C\n";
+        let (_, ast) = parse_category_file(source).unwrap();
+        let dag = CategoryDAG::from_ast(&ast).unwrap();
+        let diagnostics = lint_mixed_signedness(&dag);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "mixed-signedness");
+        assert!(diagnostics[0].message.contains("`g` maps signed B to unsigned C"));
+    }
+
+    #[test]
+    fn test_lint_clock_domain_crossings() {
+        let source = "clock clk_a\nclock clk_b\nobject A @ clk_a\nobject B @ clk_a\nobject C @ clk_b\nobject D\nmorphism f: A -> B\nmorphism g: B -> C\nmorphism h: C -> D\n";
+        let (_, ast) = parse_category_file(source).unwrap();
+        let dag = CategoryDAG::from_ast(&ast).unwrap();
+        let diagnostics = lint_clock_domain_crossings(&dag);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "clock-domain-crossing");
+        assert!(diagnostics[0].message.contains("`g` maps B on clock clk_a to C on clock clk_b"));
+    }
+
+    #[test]
+    fn test_lint_narrowing() {
+        let (_, ast) = parse_category_file("object A : 16\nobject B : 8\nobject C : 16\nmorphism f: A -> B = in\nmorphism g: B -> C = in\n").unwrap();
+        let diagnostics = lint_narrowing(&CategoryDAG::from_ast(&ast).unwrap());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "narrowing-path");
+        assert_eq!(
+            diagnostics[0].message,
+            "`g ∘ f` narrows A (16 bits) to B (8 bits) and widens again to C (16 bits); the upper bits are lost"
+        );
+
+        let (_, ast) = parse_category_file("object A : 16\nobject B : 8\nobject C : 4\nmorphism f: A -> B = in\nmorphism g: B -> C = in\n").unwrap();
+        assert!(lint_narrowing(&CategoryDAG::from_ast(&ast).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_lint_initial_values() {
+        let source = "object A = 1\nobject B = 'h2\nobject C = 3\nseq morphism r: A -> B\nmorphism f: B -> C\n";
+        let (_, ast) = parse_category_file(source).unwrap();
+        let dag = CategoryDAG::from_ast(&ast).unwrap();
+        let diagnostics = lint_initial_values(&dag);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(|d| d.code == "ineffective-initial"));
+        assert!(diagnostics[0].message.contains("`A`"));
+        assert!(diagnostics[1].message.contains("`C`"));
+    }
+
+    #[test]
+    fn test_lint_feedback_and_zero_width() {
+        let ast = ast(vec![
+            object("A"),
+            object("B"),
+            morphism("f", "A", "B"),
+            morphism("g", "B", "A"),
+        ]);
+        let mut dag = CategoryDAG::from_ast(&ast).unwrap();
+        assert_eq!(lint_feedback(&dag).len(), 1);
+        assert!(lint_zero_width(&dag).is_empty());
+
+        // A register in the loop breaks it
+        let (_, registered) = parse_category_file("object A\nobject S\nseq morphism r: A -> S = in + 1\nmorphism q: S -> A = in\n").unwrap();
+        let registered = CategoryDAG::from_ast(&registered).unwrap();
+        assert!(lint_feedback(&registered).is_empty());
+        assert!(registered.get_execution_order().is_ok());
+
+        let edge = dag.graph.edge_indices().next().unwrap();
+        dag.graph[edge] = DAGEdge { width: 0 };
+        assert_eq!(lint_zero_width(&dag).len(), 1);
+    }
+}
@@ -0,0 +1,232 @@
+//! Reading descriptions from disk, splicing in the files they import
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ast::{namespaced, CategoryAST, Statement};
+use crate::parser::{parse_category_file, whitespace};
+
+fn parse_line(line: &str) -> Result<Vec<Statement>, String> {
+    let line = line.trim();
+    // Skip lines holding nothing but comments
+    if matches!(whitespace(line), Ok(("", _))) { return Ok(Vec::new()); }
+
+    match parse_category_file(line) {
+        Ok((rest, ast)) if matches!(whitespace(rest), Ok(("", _))) => Ok(ast.statements),
+        Ok((rest, _)) => Err(format!("unexpected trailing input `{}`", rest)),
+        Err(e) => Err(format!("{}", e)),
+    }
+}
+
+/// Whether a `/*` comment or a `{` block is still open at the end of the
+/// text; braces inside comments and strings don't count
+fn is_unfinished(text: &str) -> bool {
+    let mut depth = 0usize;
+    let mut in_block = false;
+    for line in text.lines() {
+        let mut rest = line;
+        while !rest.is_empty() {
+            if in_block {
+                match rest.find("*/") {
+                    Some(end) => { in_block = false; rest = &rest[end + 2..]; }
+                    None => break,
+                }
+            } else if rest.starts_with("//") {
+                break;
+            } else if rest.starts_with("/*") {
+                in_block = true;
+                rest = &rest[2..];
+            } else if let Some(string) = rest.strip_prefix('"') {
+                // Strings end on the same line, with `\"` and `\\` escaped
+                let mut chars = string.char_indices();
+                let mut end = string.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => { chars.next(); }
+                        '"' => { end = i + 1; break; }
+                        _ => {}
+                    }
+                }
+                rest = &string[end..];
+            } else {
+                let c = rest.chars().next().unwrap();
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    in_block || depth > 0
+}
+
+/// Parse a description line by line, joining lines until every `{` block
+/// and `/*` comment is closed. Imports are left unresolved
+pub fn parse_source(text: &str) -> Result<Vec<Statement>, String> {
+    let mut statements = Vec::new();
+    let mut pending = String::new();
+    let mut start = 0;
+    for (i, line) in text.lines().enumerate() {
+        if pending.is_empty() {
+            start = i + 1;
+        }
+        pending.push_str(line);
+        pending.push('\n');
+        if is_unfinished(&pending) {
+            continue;
+        }
+        let stmts = parse_line(&pending).map_err(|e| format!("Parse error on line {}: {}", start, e))?;
+        statements.extend(stmts);
+        pending.clear();
+    }
+    if !pending.is_empty() {
+        return Err(format!("Parse error on line {}: unclosed `{{` or `/*`", start));
+    }
+    Ok(statements)
+}
+
+/// Read a description and every file it imports, relative to the importing
+/// file. Each file is spliced in where it is first imported, under the
+/// prefix of its alias; later imports of it are skipped
+pub fn load_file(path: &Path) -> Result<CategoryAST, String> {
+    Loader::default().load(path, None, "")
+}
+
+#[derive(Default)]
+struct Loader {
+    /// Files being read, outermost first
+    stack: Vec<PathBuf>,
+    /// Files already spliced in, by the full prefix they were imported under
+    loaded: HashSet<(PathBuf, String)>,
+}
+
+impl Loader {
+    fn load(&mut self, path: &Path, alias: Option<&str>, scope: &str) -> Result<CategoryAST, String> {
+        let canonical = fs::canonicalize(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        if let Some(start) = self.stack.iter().position(|file| *file == canonical) {
+            let cycle: Vec<String> = self.stack[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|file| file.display().to_string())
+                .collect();
+            return Err(format!("Import cycle: {}", cycle.join(" -> ")));
+        }
+        if !self.loaded.insert((canonical.clone(), scope.to_string())) {
+            return Ok(CategoryAST::new());
+        }
+
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let statements = parse_source(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.stack.push(canonical);
+        let base = path.parent().unwrap_or(Path::new(""));
+        let mut ast = CategoryAST::new();
+        for stmt in statements {
+            let statements = match stmt {
+                Statement::Import { path: import, alias } => {
+                    let scope = match &alias {
+                        Some(alias) if !scope.is_empty() => format!("{}_{}", scope, alias),
+                        Some(alias) => alias.clone(),
+                        None => scope.to_string(),
+                    };
+                    self.load(&base.join(import), alias.as_deref(), &scope)?
+                }
+                stmt => CategoryAST { statements: vec![stmt] },
+            };
+            ast.merge(statements).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        self.stack.pop();
+
+        Ok(match alias {
+            Some(alias) => CategoryAST { statements: namespaced(ast.statements, alias) },
+            None => ast,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dag::CategoryDAG;
+    use crate::parser::parse_statements;
+
+    /// Write the files into a fresh directory and return its path
+    fn files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cat2verilog-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (name, text) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_parse_source() {
+        let statements = parse_source("object A // input\nsection s {\n  object B\n}\n").unwrap();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(parse_source("object A; object B;\nobject C").unwrap().len(), 3);
+        assert!(parse_source("object A\nobject\n").unwrap_err().starts_with("Parse error on line 2: "));
+        assert_eq!(parse_source("object A\nsection s {\n").unwrap_err(), "Parse error on line 2: unclosed `{` or `/*`");
+        // Braces and comment markers inside a string are its text
+        let pragmas = parse_source("object A\nobject B\nmorphism f: A -> B\npragma f \"(* note = \\\"{ // /*\\\" *)\"\nobject C\n").unwrap();
+        assert_eq!(pragmas.len(), 5);
+        assert!(matches!(&pragmas[3], Statement::Pragma { text, .. } if text == "(* note = \"{ // /*\" *)"), "{:?}", pragmas[3]);
+    }
+
+    #[test]
+    fn test_import() {
+        let dir = files("import", &[
+            ("main.cat", "import \"lib/adders.cat\" as adders\nimport \"lib/adders.cat\" as adders\nobject X : 8\nmorphism f: X -> adders_A = in\n"),
+            ("lib/adders.cat", "import \"common.cat\"\nobject A : W\nmorphism inc: A -> A = in + 1\n"),
+            ("lib/common.cat", "object W : 8\n"),
+        ]);
+        let ast = load_file(&dir.join("main.cat")).unwrap();
+        let names: Vec<String> = ast.statements.iter().flat_map(Statement::declared_names).collect();
+        assert_eq!(names, ["adders_W", "adders_A", "adders_inc", "X", "f"]);
+        assert!(CategoryDAG::from_ast(&ast).is_ok());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_diamond_import() {
+        let dir = files("diamond", &[
+            ("main.cat", "import \"left.cat\"\nimport \"right.cat\"\nimport \"left.cat\" as other\n"),
+            ("left.cat", "import \"base.cat\"\nobject L : B\n"),
+            ("right.cat", "import \"base.cat\"\nobject R : B\n"),
+            ("base.cat", "object B : 4\n"),
+        ]);
+        let ast = load_file(&dir.join("main.cat")).unwrap();
+        let names: Vec<String> = ast.statements.iter().flat_map(Statement::declared_names).collect();
+        // The aliased import is a separate copy with its own base
+        assert_eq!(names, ["B", "L", "R", "other_B", "other_L"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let dir = files("import-errors", &[
+            ("a.cat", "import \"b.cat\"\n"),
+            ("b.cat", "object B\nimport \"a.cat\"\n"),
+            ("clash.cat", "object B\nimport \"b2.cat\"\n"),
+            ("b2.cat", "object B\n"),
+            ("missing.cat", "import \"nowhere.cat\"\n"),
+            ("bad.cat", "import \"broken.cat\"\n"),
+            ("broken.cat", "object A\nmorphism\n"),
+        ]);
+        let err = load_file(&dir.join("a.cat")).unwrap_err();
+        assert!(err.starts_with("Import cycle: ") && err.ends_with("a.cat"), "{}", err);
+        assert!(err.contains("b.cat -> "), "{}", err);
+        assert_eq!(load_file(&dir.join("clash.cat")).unwrap_err(), format!("{}: B is already declared", dir.join("clash.cat").display()));
+        assert!(load_file(&dir.join("missing.cat")).unwrap_err().starts_with(&format!("Cannot read {}", dir.join("nowhere.cat").display())));
+        assert!(load_file(&dir.join("bad.cat")).unwrap_err().starts_with(&format!("{}: Parse error on line 2", dir.join("broken.cat").display())));
+        fs::remove_dir_all(dir).unwrap();
+
+        let (_, statements) = parse_statements("import \"lib.cat\"").unwrap();
+        let err = CategoryDAG::from_ast(&CategoryAST { statements }).err();
+        assert_eq!(err.as_deref(), Some("Import of \"lib.cat\" is unresolved; load the description with loader::load_file"));
+    }
+}
@@ -0,0 +1,372 @@
+//! Rewrites of morphism body expressions applied before lowering to Verilog
+
+use crate::ast::{BinOp, Expr};
+
+/// Fold constant sub-expressions and drop identity operations: `in + 1 - 1`
+/// becomes `in` and `in * 1 | 0` becomes `in`.
+///
+/// Only rewrites that hold at every signal width are applied. Additions and
+/// subtractions of constants are merged since they wrap consistently, but a
+/// shift pair like `(in << 2) >> 2` is kept because the left shift may drop
+/// the upper bits of `in`.
+pub fn fold_constants(expr: &Expr) -> Expr {
+    // A constant condition picks its branch
+    if let Expr::If { condition, then, otherwise } = expr {
+        return match fold_constants(condition) {
+            Expr::Literal(0) => fold_constants(otherwise),
+            Expr::Literal(_) => fold_constants(then),
+            condition => Expr::If {
+                condition: Box::new(condition),
+                then: Box::new(fold_constants(then)),
+                otherwise: Box::new(fold_constants(otherwise)),
+            },
+        };
+    }
+    // A double complement cancels at every width, the inner one extending first
+    if let Expr::Not(operand) = expr {
+        return match fold_constants(operand) {
+            Expr::Not(inner) => *inner,
+            operand => Expr::Not(Box::new(operand)),
+        };
+    }
+    if let Expr::Concat(parts) = expr {
+        return Expr::Concat(parts.iter().map(fold_constants).collect());
+    }
+    let Expr::Binary { op, lhs, rhs } = expr else {
+        return expr.clone();
+    };
+    let lhs = fold_constants(lhs);
+    let rhs = fold_constants(rhs);
+
+    if let (Expr::Literal(a), Expr::Literal(b)) = (&lhs, &rhs) {
+        if let Some(value) = eval(*op, *a, *b) {
+            return Expr::Literal(value);
+        }
+    }
+
+    match (op, &lhs, &rhs) {
+        (BinOp::Add | BinOp::Sub, _, _) => fold_offset(*op, lhs, rhs),
+        (BinOp::Mul | BinOp::And, Expr::Literal(0), _) | (BinOp::Mul | BinOp::And, _, Expr::Literal(0)) => Expr::Literal(0),
+        (BinOp::Mul, Expr::Literal(1), _) | (BinOp::Or | BinOp::Xor, Expr::Literal(0), _) => rhs,
+        (BinOp::Mul, _, Expr::Literal(1)) | (BinOp::Shl | BinOp::Shr | BinOp::Or | BinOp::Xor, _, Expr::Literal(0)) => lhs,
+        _ => binary(*op, lhs, rhs),
+    }
+}
+
+/// Evaluate an operator on two constants, if the result is representable
+fn eval(op: BinOp, a: u64, b: u64) -> Option<u64> {
+    match op {
+        BinOp::Add => a.checked_add(b),
+        BinOp::Sub => a.checked_sub(b),
+        BinOp::Mul => a.checked_mul(b),
+        BinOp::Shl => u32::try_from(b).ok().and_then(|b| a.checked_shl(b)).filter(|v| v >> b == a),
+        BinOp::Shr => u32::try_from(b).ok().and_then(|b| a.checked_shr(b)),
+        BinOp::And => Some(a & b),
+        BinOp::Or => Some(a | b),
+        BinOp::Xor => Some(a ^ b),
+        BinOp::Eq => Some((a == b) as u64),
+        BinOp::Ne => Some((a != b) as u64),
+        BinOp::Lt => Some((a < b) as u64),
+        BinOp::Le => Some((a <= b) as u64),
+        BinOp::Gt => Some((a > b) as u64),
+        BinOp::Ge => Some((a >= b) as u64),
+    }
+}
+
+/// Value of an expression for the input `in`, in its low `width` bits.
+/// Arithmetic wraps, which keeps the low bits right as long as nothing
+/// shifts upper bits down or compares them; `None` for anything that does
+pub fn evaluate(expr: &Expr, input: u64, width: usize) -> Option<u64> {
+    let value = match expr {
+        Expr::Ident(name) if name == "in" => input,
+        Expr::Literal(value) => *value,
+        Expr::Slice { name, msb, lsb } if name == "in" => input.checked_shr(*lsb as u32).unwrap_or(0) & mask(msb - lsb + 1),
+        Expr::Not(operand) => !evaluate(operand, input, width)?,
+        Expr::Binary { op, lhs, rhs } => {
+            let (a, b) = (evaluate(lhs, input, width)?, evaluate(rhs, input, width)?);
+            match op {
+                BinOp::Add => a.wrapping_add(b),
+                BinOp::Sub => a.wrapping_sub(b),
+                BinOp::Mul => a.wrapping_mul(b),
+                BinOp::Shl => u32::try_from(b).ok().and_then(|b| a.checked_shl(b)).unwrap_or(0),
+                BinOp::And => a & b,
+                BinOp::Or => a | b,
+                BinOp::Xor => a ^ b,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(value & mask(width))
+}
+
+/// Low `width` bits set
+fn mask(width: usize) -> u64 {
+    u64::MAX.checked_shr(u64::BITS.saturating_sub(width as u32)).filter(|_| width > 0).unwrap_or(0)
+}
+
+/// Split an expression into a non-constant base and a constant offset
+fn split_offset(expr: Expr) -> (Option<Expr>, i128) {
+    match expr {
+        Expr::Literal(value) => (None, value as i128),
+        Expr::Binary { op: BinOp::Add, lhs, rhs } => match (*lhs, *rhs) {
+            (base, Expr::Literal(value)) | (Expr::Literal(value), base) => (Some(base), value as i128),
+            (lhs, rhs) => (Some(binary(BinOp::Add, lhs, rhs)), 0),
+        },
+        Expr::Binary { op: BinOp::Sub, lhs, rhs } => match *rhs {
+            Expr::Literal(value) => (Some(*lhs), -(value as i128)),
+            rhs => (Some(binary(BinOp::Sub, *lhs, rhs)), 0),
+        },
+        other => (Some(other), 0),
+    }
+}
+
+/// Merge the constant offsets of both sides of a `+` or `-`
+fn fold_offset(op: BinOp, lhs: Expr, rhs: Expr) -> Expr {
+    // `c - x` has no base to attach a possibly negative offset to
+    if op == BinOp::Sub && matches!(lhs, Expr::Literal(_)) {
+        return binary(op, lhs, rhs);
+    }
+    let original = (lhs.clone(), rhs.clone());
+    let sign = if op == BinOp::Sub { -1 } else { 1 };
+    let (lhs_base, lhs_offset) = split_offset(lhs);
+    let (rhs_base, rhs_offset) = split_offset(rhs);
+    let offset = lhs_offset + sign * rhs_offset;
+    if offset.unsigned_abs() > u64::MAX as u128 {
+        return binary(op, original.0, original.1);
+    }
+
+    let base = match (lhs_base, rhs_base) {
+        (Some(lhs), Some(rhs)) => binary(op, lhs, rhs),
+        (Some(base), None) | (None, Some(base)) => base,
+        (None, None) => return Expr::Literal(offset as u64),
+    };
+    match offset {
+        0 => base,
+        offset if offset > 0 => binary(BinOp::Add, base, Expr::Literal(offset as u64)),
+        offset => binary(BinOp::Sub, base, Expr::Literal(offset.unsigned_abs() as u64)),
+    }
+}
+
+/// Compose two morphisms by substituting `value` for the input `in` of
+/// `body`. `None` if `body` selects bits of `in`, which can't be written
+/// over an arbitrary expression
+pub fn compose(body: &Expr, value: &Expr) -> Option<Expr> {
+    Some(match body {
+        Expr::Ident(name) if name == "in" => value.clone(),
+        Expr::Slice { name, msb, lsb } if name == "in" => match value {
+            Expr::Ident(name) => Expr::Slice { name: name.clone(), msb: *msb, lsb: *lsb },
+            _ => return None,
+        },
+        Expr::Binary { op, lhs, rhs } => binary(*op, compose(lhs, value)?, compose(rhs, value)?),
+        Expr::Not(operand) => Expr::Not(Box::new(compose(operand, value)?)),
+        Expr::Concat(parts) => Expr::Concat(parts.iter().map(|part| compose(part, value)).collect::<Option<_>>()?),
+        Expr::If { condition, then, otherwise } => Expr::If {
+            condition: Box::new(compose(condition, value)?),
+            then: Box::new(compose(then, value)?),
+            otherwise: Box::new(compose(otherwise, value)?),
+        },
+        _ => body.clone(),
+    })
+}
+
+/// Check whether any low bit of an expression's result can depend on high
+/// bits of its operands: a right shift, a comparison, a concatenation,
+/// which places its parts by their widths, or a condition tested for being
+/// nonzero, anywhere
+pub fn reads_upper_bits(expr: &Expr) -> bool {
+    match expr {
+        Expr::Binary { op, .. } if *op == BinOp::Shr || op.is_comparison() => true,
+        Expr::If { condition, .. } if !matches!(**condition, Expr::Binary { op, .. } if op.is_comparison()) => true,
+        Expr::Concat(_) => true,
+        Expr::Binary { lhs, rhs, .. } => reads_upper_bits(lhs) || reads_upper_bits(rhs),
+        Expr::Not(operand) => reads_upper_bits(operand),
+        Expr::If { condition, then, otherwise } => reads_upper_bits(condition) || reads_upper_bits(then) || reads_upper_bits(otherwise),
+        _ => false,
+    }
+}
+
+/// Binary sub-expressions occurring more than once across `bodies`, largest
+/// first and none inside another, so each can be computed once in a wire.
+///
+/// A wire holds the full result, carry included, which only matters to
+/// operators whose low result bits depend on high operand bits. So neither
+/// operand of `>>` or of a comparison nor a shift amount, which Verilog
+/// sizes on its own, is ever shared. The wire is then read in a context at
+/// least as wide as itself, so only expressions whose result zero-extends
+/// to that width are shared: a subtraction or `~` would have set the extra
+/// bits.
+pub fn common_subexpressions(bodies: &[&Expr]) -> Vec<Expr> {
+    let mut occurrences: Vec<(Expr, usize)> = Vec::new();
+    for body in bodies {
+        count_shareable(body, &mut occurrences);
+    }
+    let mut repeated: Vec<Expr> = occurrences
+        .into_iter()
+        .filter(|(expr, count)| *count > 1 && zero_extends(expr))
+        .map(|(expr, _)| expr)
+        .collect();
+    repeated.sort_by_key(|expr| std::cmp::Reverse(size(expr)));
+
+    let mut shared: Vec<Expr> = Vec::new();
+    for expr in repeated {
+        if !shared.iter().any(|outer| contains(outer, &expr)) {
+            shared.push(expr);
+        }
+    }
+    shared
+}
+
+/// Replace every shareable occurrence of `target` in `expr`, as found by
+/// [`common_subexpressions`], with `replacement`
+pub fn replace_subexpression(expr: &Expr, target: &Expr, replacement: &Expr) -> Expr {
+    if expr == target {
+        return replacement.clone();
+    }
+    let Expr::Binary { op, lhs, rhs } = expr else {
+        return expr.clone();
+    };
+    match op {
+        _ if *op == BinOp::Shr || op.is_comparison() => expr.clone(),
+        BinOp::Shl => binary(*op, replace_subexpression(lhs, target, replacement), *rhs.clone()),
+        _ => binary(
+            *op,
+            replace_subexpression(lhs, target, replacement),
+            replace_subexpression(rhs, target, replacement),
+        ),
+    }
+}
+
+fn count_shareable(expr: &Expr, occurrences: &mut Vec<(Expr, usize)>) {
+    let Expr::Binary { op, lhs, rhs } = expr else {
+        return;
+    };
+    match occurrences.iter_mut().find(|(seen, _)| seen == expr) {
+        Some((_, count)) => *count += 1,
+        None => occurrences.push((expr.clone(), 1)),
+    }
+    match op {
+        _ if *op == BinOp::Shr || op.is_comparison() => {}
+        BinOp::Shl => count_shareable(lhs, occurrences),
+        _ => {
+            count_shareable(lhs, occurrences);
+            count_shareable(rhs, occurrences);
+        }
+    }
+}
+
+/// Check whether an expression's value at the width `infer_width` gives it,
+/// zero-extended, is its value at any wider width. Operands of a comparison
+/// are sized by themselves, so any comparison does
+fn zero_extends(expr: &Expr) -> bool {
+    match expr {
+        Expr::Binary { op, .. } if op.is_comparison() => true,
+        Expr::Binary { op: BinOp::Sub, .. } | Expr::Not(_) => false,
+        // Only a constant shift amount counts in the inferred width
+        Expr::Binary { op: BinOp::Shl, lhs, rhs } => matches!(**rhs, Expr::Literal(_)) && zero_extends(lhs),
+        Expr::Binary { op: BinOp::Shr, lhs, .. } => zero_extends(lhs),
+        Expr::Binary { lhs, rhs, .. } => zero_extends(lhs) && zero_extends(rhs),
+        Expr::If { then, otherwise, .. } => zero_extends(then) && zero_extends(otherwise),
+        Expr::Ident(_) | Expr::Literal(_) | Expr::Slice { .. } | Expr::Concat(_) => true,
+    }
+}
+
+fn size(expr: &Expr) -> usize {
+    match expr {
+        Expr::Binary { lhs, rhs, .. } => 1 + size(lhs) + size(rhs),
+        _ => 1,
+    }
+}
+
+fn contains(outer: &Expr, inner: &Expr) -> bool {
+    outer == inner || matches!(outer, Expr::Binary { lhs, rhs, .. } if contains(lhs, inner) || contains(rhs, inner))
+}
+
+fn binary(op: BinOp, lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::expr;
+
+    fn fold(source: &str) -> String {
+        let (rest, parsed) = expr(source).unwrap();
+        assert_eq!(rest, "");
+        fold_constants(&parsed).to_source()
+    }
+
+    #[test]
+    fn test_fold_offsets() {
+        assert_eq!(fold("in + 1 - 1"), "in");
+        assert_eq!(fold("in - 3 + 1"), "in - 2");
+        assert_eq!(fold("2 + in + 3"), "in + 5");
+        assert_eq!(fold("in + 0"), "in");
+        assert_eq!(fold("1 - (in + 3)"), "1 - (in + 3)");
+    }
+
+    #[test]
+    fn test_fold_constants_and_identities() {
+        assert_eq!(fold("(2 + 3) * in"), "5 * in");
+        assert_eq!(fold("in * 1 | 0"), "in");
+        assert_eq!(fold("in & 0"), "0");
+        assert_eq!(fold("in << 0 >> 0"), "in");
+        assert_eq!(fold("1 << 4 | 2"), "18");
+        assert_eq!(fold("(in << 2) >> 2"), "in << 2 >> 2");
+        assert_eq!(fold("if 2 > 1 then in else 0"), "in");
+        assert_eq!(fold("~~(in ^ 0) ^ 6 ^ 3"), "in ^ 6 ^ 3");
+        assert_eq!(fold("~(0 ^ in) & (6 ^ 3)"), "~in & 5");
+        assert_eq!(fold("{in + 0, in[3:0] * 1}"), "{in, in[3:0]}");
+        assert_eq!(fold("if in < 3 + 1 then in * 1 else 0"), "if in < 4 then in else 0");
+    }
+
+    #[test]
+    fn test_compose() {
+        let parse = |source: &str| expr(source).unwrap().1;
+        let composed = compose(&parse("in * 2 - 1"), &parse("in + 1")).unwrap();
+        assert_eq!(composed.to_source(), "(in + 1) * 2 - 1");
+        assert_eq!(fold_constants(&compose(&parse("in - 1"), &parse("in + 1")).unwrap()).to_source(), "in");
+        assert_eq!(compose(&parse("in[3:0]"), &parse("in")).unwrap().to_source(), "in[3:0]");
+        assert_eq!(compose(&parse("in[3:0]"), &parse("in + 1")), None);
+        assert!(reads_upper_bits(&parse("in + (in >> 1)")));
+        assert!(reads_upper_bits(&parse("if in > 3 then in else 0")));
+        assert!(!reads_upper_bits(&parse("in << 1")));
+        assert!(reads_upper_bits(&parse("if in then 1 else 0")));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let value = |source: &str, input: u64, width: usize| evaluate(&expr(source).unwrap().1, input, width);
+        assert_eq!(value("(in + 1) * 2 - 1", 3, 8), Some(7));
+        assert_eq!(value("in - 1", 0, 8), Some(0xFF));
+        assert_eq!(value("~in & 15", 5, 8), Some(10));
+        assert_eq!(value("in[7:4] << 1", 0xA5, 8), Some(0x14));
+        assert_eq!(value("in >> 1", 4, 8), None);
+    }
+
+    #[test]
+    fn test_common_subexpressions() {
+        let parse = |source: &str| expr(source).unwrap().1;
+        let shared = |sources: &[&str]| -> Vec<String> {
+            let bodies: Vec<Expr> = sources.iter().map(|source| parse(source)).collect();
+            let bodies: Vec<&Expr> = bodies.iter().collect();
+            common_subexpressions(&bodies).iter().map(Expr::to_source).collect()
+        };
+        assert_eq!(shared(&["(in + 1) * 2", "(in + 1) | 4"]), ["in + 1"]);
+        assert_eq!(shared(&["(in + 1) * (in + 1)"]), ["in + 1"]);
+        // The largest repeated expression is shared, not its parts
+        assert_eq!(shared(&["(in + 1) * 2 - 3", "(in + 1) * 2 & 7"]), ["(in + 1) * 2"]);
+        // A right shift would see the carry a wire keeps
+        assert!(shared(&["(in + 1) >> 1", "(in + 1) * 2"]).is_empty());
+        assert!(shared(&["in + 1", "in - 1"]).is_empty());
+        // A borrow or a complement would reach the bits of a wider context
+        assert!(shared(&["(in - 1) * 2", "(in - 1) | 4"]).is_empty());
+        assert!(shared(&["~in & 3", "~in | 4"]).is_empty());
+        assert_eq!(shared(&["(in - 1) * (in << 2)", "(in << 2) | 4"]), ["in << 2"]);
+        assert!(shared(&["(in + 1) == 3", "(in + 1) | 4"]).is_empty());
+
+        let replaced = replace_subexpression(&parse("(in + 1) * 2 + ((in + 1) >> 1)"), &parse("in + 1"), &Expr::Ident("cse0".to_string()));
+        assert_eq!(replaced.to_source(), "cse0 * 2 + (in + 1 >> 1)");
+    }
+}
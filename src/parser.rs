@@ -1,123 +1,2343 @@
 use nom::{
     branch::alt,
     bytes::complete::*,
-    character::complete::{alpha1, alphanumeric1, space0, space1, line_ending, char},
+    character::complete::{alpha1, alphanumeric1, digit1, none_of, one_of, space0, space1, line_ending, not_line_ending},
     combinator::*,
-    multi::{many0, many1, separated_list1},
+    multi::{many0, many1, separated_list0, separated_list1},
     sequence::*,
     IResult,
 };
 
-use crate::ast::{Statement, CategoryAST};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
 
-/// Parse an identifier (alphanumeric characters)
+use crate::ast::{family_member, identity_name, namespaced, unrolled, Attribute, BinOp, Bindings, Composition, Expr, MorphismKind, Statement, CategoryAST, WidthExpr, STD_REDUCTIONS, UNIT_OBJECT};
+
+/// Parse an identifier: alphanumeric characters, or any name in double
+/// quotes taken literally: object "my-obj". A plain name may be scoped by
+/// the blocks declaring it, whose prefixes it spells out: Alu.add is Alu_add
 fn identifier(input: &str) -> IResult<&str, String> {
+    let (input, name) = alt((
+        map(
+            pair(plain_identifier, many0(preceded(tag("."), plain_identifier))),
+            |(first, scopes)| scopes.into_iter().fold(first, |path, name| format!("{}_{}", path, name)),
+        ),
+        map(delimited(tag("\""), is_not("\"\r\n"), tag("\"")), |s: &str| s.to_string()),
+    ))(input)?;
+    indexed(input, name)
+}
+
+/// Parse the indices after a name: a member of an object family, A[3], or
+/// one indexed by the variable of an enclosing loop, A[i], which the loop
+/// fills in
+fn indexed(input: &str, name: String) -> IResult<&str, String> {
+    let (input, indices) = many0(delimited(
+        tag("["),
+        alt((map(integer, Ok), map(plain_identifier, Err))),
+        tag("]"),
+    ))(input)?;
+    Ok((input, indices.into_iter().fold(name, |name, index| match index {
+        Ok(index) => family_member(&name, index),
+        Err(variable) => format!("{}[{}]", name, variable),
+    })))
+}
+
+/// Parse an identifier in a composition chain. `Alu.add` is kept as
+/// written: whether it names a scoped morphism or composes two is only
+/// known once every name is declared, see `chain_names`
+fn chain_identifier(input: &str) -> IResult<&str, String> {
+    alt((
+        map(
+            separated_list1(tag("."), |input| plain_identifier(input).and_then(|(input, name)| indexed(input, name))),
+            |names| names.join("."),
+        ),
+        map(delimited(tag("\""), is_not("\"\r\n"), tag("\"")), |s: &str| s.to_string()),
+    ))(input)
+}
+
+/// Parse an unquoted identifier, which is also a valid Verilog name
+fn plain_identifier(input: &str) -> IResult<&str, String> {
     map(
         recognize(pair(
-            alt((alpha1, char('_'))),
-            many0(alt((alphanumeric1, char('_')))),
+            alt((alpha1, tag("_"))),
+            many0(alt((alphanumeric1, tag("_")))),
+        )),
+        |s: &str| s.to_string(),
+    )(input)
+}
+
+/// Parse a conditional compilation guard: @ifdef DEBUG
+fn guard(input: &str) -> IResult<&str, String> {
+    preceded(tuple((space0, tag("@ifdef"), space1)), plain_identifier)(input)
+}
+
+/// Parse the file of a black-box morphism: extern "f_impl.v"
+fn external(input: &str) -> IResult<&str, String> {
+    map(
+        preceded(tuple((space0, tag("extern"), space1)), delimited(tag("\""), is_not("\"\r\n"), tag("\""))),
+        |s: &str| s.to_string(),
+    )(input)
+}
+
+/// Parse a line comment up to the end of the line, // note, or a block
+/// comment that may span lines, /* note */
+fn comment(input: &str) -> IResult<&str, &str> {
+    alt((
+        recognize(pair(tag("//"), not_line_ending)),
+        recognize(tuple((tag("/*"), take_until("*/"), tag("*/")))),
+    ))(input)
+}
+
+/// Parse whitespace, including line breaks and comments
+pub fn whitespace(input: &str) -> IResult<&str, &str> {
+    recognize(many0(alt((space1, line_ending, comment))))(input)
+}
+
+/// Parse spaces and comments up to the end of a statement
+fn trailing_space(input: &str) -> IResult<&str, &str> {
+    recognize(many0(alt((space1, comment))))(input)
+}
+
+/// Parse the line breaks between statements, each of which may follow
+/// comments
+fn line_breaks(input: &str) -> IResult<&str, &str> {
+    recognize(many1(pair(trailing_space, line_ending)))(input)
+}
+
+/// Parse what separates two statements: line breaks, or a `;` that may be
+/// followed by them
+fn statement_separator(input: &str) -> IResult<&str, &str> {
+    alt((recognize(tuple((trailing_space, tag(";"), opt(line_breaks)))), line_breaks))(input)
+}
+
+/// Parse the statements of a block, separated by line breaks or `;`, the
+/// last of which may also end with `;`
+fn statement_list(input: &str) -> IResult<&str, Vec<Statement>> {
+    let (input, statements) = separated_list0(statement_separator, parse_statements)(input)?;
+    let (input, _) = opt(pair(trailing_space, tag(";")))(input)?;
+    Ok((input, statements.into_iter().flatten().collect()))
+}
+
+/// Parse an unsigned integer literal
+fn integer(input: &str) -> IResult<&str, usize> {
+    map_res(digit1, |s: &str| s.parse::<usize>())(input)
+}
+
+/// Parse a width atom: integer, the wider of two widths, object name or
+/// parenthesized expression
+fn width_atom(input: &str) -> IResult<&str, WidthExpr> {
+    alt((
+        map(integer, WidthExpr::Const),
+        map(
+            delimited(
+                tuple((tag("max"), space0, tag("("), space0)),
+                separated_pair(width_expr, tuple((space0, tag(","), space0)), width_expr),
+                pair(space0, tag(")")),
+            ),
+            |(lhs, rhs)| WidthExpr::Max(Box::new(lhs), Box::new(rhs)),
+        ),
+        map(identifier, WidthExpr::Ref),
+        delimited(pair(tag("("), space0), width_expr, pair(space0, tag(")"))),
+    ))(input)
+}
+
+/// Parse a width product: A * 2
+fn width_term(input: &str) -> IResult<&str, WidthExpr> {
+    let (input, first) = width_atom(input)?;
+    let (input, rest) = many0(preceded(delimited(space0, tag("*"), space0), width_atom))(input)?;
+    Ok((input, rest.into_iter().fold(first, |acc, rhs| WidthExpr::Mul(Box::new(acc), Box::new(rhs)))))
+}
+
+/// Parse a width expression: A + 1
+pub fn width_expr(input: &str) -> IResult<&str, WidthExpr> {
+    let (input, first) = width_term(input)?;
+    let (input, rest) = many0(preceded(delimited(space0, tag("+"), space0), width_term))(input)?;
+    Ok((input, rest.into_iter().fold(first, |acc, rhs| WidthExpr::Add(Box::new(acc), Box::new(rhs)))))
+}
+
+/// Parse the `signed` keyword, but not an identifier starting with it
+fn signed_keyword(input: &str) -> IResult<&str, &str> {
+    terminated(tag("signed"), not(alt((alphanumeric1, tag("_")))))(input)
+}
+
+/// Parse the `active_low` polarity keyword
+fn active_low_keyword(input: &str) -> IResult<&str, &str> {
+    terminated(tag("active_low"), not(alt((alphanumeric1, tag("_")))))(input)
+}
+
+/// Parse a fixed-point format, integer bits then fraction bits, as its
+/// total width and fraction: fixed<8,4>
+fn fixed_point(input: &str) -> IResult<&str, (WidthExpr, Option<usize>)> {
+    map(
+        delimited(
+            tuple((tag("fixed"), space0, tag("<"), space0)),
+            separated_pair(integer, tuple((space0, tag(","), space0)), integer),
+            pair(space0, tag(">")),
+        ),
+        |(integer, fraction)| (WidthExpr::Const(integer + fraction), Some(fraction)),
+    )(input)
+}
+
+/// Parse an object's width, with the fraction bits of a fixed-point format:
+/// A + 1 or fixed<8,4>
+pub fn object_width(input: &str) -> IResult<&str, (WidthExpr, Option<usize>)> {
+    alt((fixed_point, map(width_expr, |width| (width, None))))(input)
+}
+
+/// Parse an object's type: A + 1, signed 16, fixed<8,4>, or just signed
+fn object_type(input: &str) -> IResult<&str, (Option<WidthExpr>, bool, Option<usize>)> {
+    let width = || preceded(not(active_low_keyword), object_width);
+    preceded(
+        tuple((space0, tag(":"), space0)),
+        alt((
+            map(preceded(pair(signed_keyword, space1), width()), |(width, fraction)| (Some(width), true, fraction)),
+            map(signed_keyword, |_| (None, true, None)),
+            map(width(), |(width, fraction)| (Some(width), false, fraction)),
+        )),
+    )(input)
+}
+
+/// Parse an optional trailing polarity: ` active_low`
+fn polarity(input: &str) -> IResult<&str, bool> {
+    map(opt(preceded(space1, active_low_keyword)), |keyword| keyword.is_some())(input)
+}
+
+/// Parse an optional clock domain: ` @ clk_a`
+fn domain(input: &str) -> IResult<&str, Option<String>> {
+    opt(preceded(tuple((space0, tag("@"), space0)), identifier))(input)
+}
+
+/// Parse a Verilog number literal: 60, 'd60, 8'h3C, 4'sb1x0z
+pub fn verilog_number(input: &str) -> IResult<&str, String> {
+    map(
+        alt((
+            recognize(tuple((
+                opt(digit1),
+                tag("'"),
+                opt(one_of("sS")),
+                one_of("bBoOdDhH"),
+                take_while1(|c: char| c.is_ascii_hexdigit() || "xXzZ_".contains(c)),
+            ))),
+            digit1,
         )),
         |s: &str| s.to_string(),
     )(input)
 }
 
-/// Parse whitespace
-fn whitespace(input: &str) -> IResult<&str, &str> {
-    recognize(many0(alt((space1, line_ending))))(input)
+/// Parse a simulation initial value: `= 8'h3C`
+fn initial_value(input: &str) -> IResult<&str, String> {
+    preceded(tuple((space0, tag("="), space0)), verilog_number)(input)
 }
 
-/// Parse object declaration: object A
+/// Parse object declaration: object A, object Sum : A + 1, object D : signed 8,
+/// object R : 8 = 8'h3C, object rst_n : 1 active_low
 pub fn parse_object(input: &str) -> IResult<&str, Statement> {
     let (input, _) = tag("object")(input)?;
     let (input, _) = space1(input)?;
     let (input, name) = identifier(input)?;
-    let (input, _) = opt(whitespace)(input)?;
-    Ok((input, Statement::Object(name)))
+    let (input, ty) = opt(object_type)(input)?;
+    let (input, active_low) = polarity(input)?;
+    let (input, domain) = domain(input)?;
+    let (input, initial) = opt(initial_value)(input)?;
+    let (input, _) = space0(input)?;
+    let (width, signed, fraction) = ty.unwrap_or((None, false, None));
+    Ok((input, Statement::Object { name, width, signed, initial, active_low, fraction, domain }))
 }
 
-/// Parse morphism declaration: morphism f: A -> B
-pub fn parse_morphism(input: &str) -> IResult<&str, Statement> {
-    let (input, _) = tag("morphism")(input)?;
+/// Parse product object declaration, with `*` as the ASCII alias of `×`:
+/// object C = A × B
+pub fn parse_product(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("object")(input)?;
     let (input, _) = space1(input)?;
     let (input, name) = identifier(input)?;
-    let (input, _) = tag(":")(input)?;
+    let (input, _) = tuple((space0, tag("="), space0))(input)?;
+    let (input, first) = identifier(input)?;
+    let (input, rest) = many1(preceded(delimited(space0, alt((tag("×"), tag("*"))), space0), identifier))(input)?;
     let (input, _) = space0(input)?;
-    let (input, from) = identifier(input)?;
+    let mut components = vec![first];
+    components.extend(rest);
+    Ok((input, Statement::Product { name, components }))
+}
+
+/// Parse coproduct object declaration: object C = A + B
+pub fn parse_coproduct(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("object")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0))(input)?;
+    let (input, first) = identifier(input)?;
+    let (input, rest) = many1(preceded(delimited(space0, tag("+"), space0), identifier))(input)?;
     let (input, _) = space0(input)?;
-    let (input, _) = tag("->")(input)?;
+    let mut components = vec![first];
+    components.extend(rest);
+    Ok((input, Statement::Coproduct { name, components }))
+}
+
+/// Parse enumerated object declaration: object State = { IDLE, RUN, DONE }
+pub fn parse_enum(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("object")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0, tag("{"), space0))(input)?;
+    let (input, members) = separated_list1(tuple((space0, tag(","), space0)), plain_identifier)(input)?;
+    let (input, _) = tuple((space0, tag("}"), space0))(input)?;
+    Ok((input, Statement::Enum { name, members }))
+}
+
+/// Parse memory object declaration, word width before depth:
+/// object Mem : ram 8 x 256
+pub fn parse_memory(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("object")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag(":"), space0, tag("ram"), space1))(input)?;
+    let (input, width) = width_expr(input)?;
+    let (input, _) = tuple((space1, tag("x"), space1))(input)?;
+    let (input, depth) = verify(integer, |depth| *depth > 0)(input)?;
     let (input, _) = space0(input)?;
-    let (input, to) = identifier(input)?;
-    let (input, _) = opt(whitespace)(input)?;
-    Ok((input, Statement::Morphism { name, from, to }))
+    Ok((input, Statement::Memory { name, width, depth }))
 }
 
-/// Parse composition operator: ∘
-fn parse_composition_op(input: &str) -> IResult<&str, &str> {
-    delimited(space0, tag("∘"), space0)(input)
+/// Parse a family of objects over an inclusive index range: object A[0..7] : 8
+pub fn parse_family(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("object")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = plain_identifier(input)?;
+    let (input, (start, end)) = verify(
+        delimited(tag("["), separated_pair(integer, tag(".."), integer), tag("]")),
+        |(start, end)| start <= end,
+    )(input)?;
+    let (input, ty) = opt(object_type)(input)?;
+    let (input, _) = space0(input)?;
+    let (width, signed, fraction) = ty.unwrap_or((None, false, None));
+    Ok((input, Statement::ObjectFamily { name, start, end, width, signed, fraction }))
 }
 
-/// Parse commutativity assertion: assert commute: g ∘ f == h
-pub fn parse_assert_commute(input: &str) -> IResult<&str, Statement> {
-    let (input, _) = tag("assert commute:")(input)?;
+/// Parse a declaration of several objects sharing one type:
+/// object A, B, C : signed 8
+pub fn parse_object_list(input: &str) -> IResult<&str, Vec<Statement>> {
+    let (input, _) = tag("object")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, names) = separated_list1(delimited(space0, tag(","), space0), identifier)(input)?;
+    let (input, ty) = opt(object_type)(input)?;
+    let (input, active_low) = polarity(input)?;
+    let (input, domain) = domain(input)?;
+    let (input, initial) = opt(initial_value)(input)?;
     let (input, _) = space0(input)?;
-    let (input, lhs) = separated_list1(parse_composition_op, identifier)(input)?;
+    let (width, signed, fraction) = ty.unwrap_or((None, false, None));
+    let objects = names
+        .into_iter()
+        .map(|name| Statement::Object { name, width: width.clone(), signed, initial: initial.clone(), active_low, fraction, domain: domain.clone() })
+        .collect();
+    Ok((input, objects))
+}
+
+/// Parse clock domain declaration: clock clk_a
+pub fn parse_clock(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("clock")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
     let (input, _) = space0(input)?;
-    let (input, _) = tag("==")(input)?;
+    Ok((input, Statement::Clock { name }))
+}
+
+/// Parse a double-quoted string in which `\"` and `\\` stand for `"` and `\`
+fn string_literal(input: &str) -> IResult<&str, String> {
+    delimited(
+        tag("\""),
+        map(
+            opt(escaped_transform(none_of("\\\"\r\n"), '\\', alt((value("\\", tag("\\")), value("\"", tag("\"")))))),
+            Option::unwrap_or_default,
+        ),
+        tag("\""),
+    )(input)
+}
+
+/// Parse a Verilog attribute attached to an object or morphism:
+/// pragma f "(* keep_hierarchy = \"yes\" *)"
+pub fn parse_pragma(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("pragma")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, target) = identifier(input)?;
+    let (input, _) = space1(input)?;
+    let (input, text) = string_literal(input)?;
+    let (input, _) = trailing_space(input)?;
+    Ok((input, Statement::Pragma { target, text }))
+}
+
+/// Parse the name of the top module: top MyDesign
+pub fn parse_top(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("top")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = plain_identifier(input)?;
+    let (input, _) = trailing_space(input)?;
+    Ok((input, Statement::Top { name }))
+}
+
+/// Parse named wire declaration: wire mid, wire mid : 8
+pub fn parse_wire(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("wire")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, width) = opt(endpoint_width)(input)?;
     let (input, _) = space0(input)?;
-    let (input, rhs) = separated_list1(parse_composition_op, identifier)(input)?;
-    let (input, _) = opt(whitespace)(input)?;
-    Ok((input, Statement::AssertCommute { lhs, rhs }))
+    Ok((input, Statement::Wire { name, width }))
 }
 
-/// Parse a single statement line
-pub fn parse_statement(input: &str) -> IResult<&str, Statement> {
-    let (input, _) = opt(whitespace)(input)?;
-    let result = alt((parse_object, parse_morphism, parse_assert_commute))(input)?;
-    let (input, _) = opt(whitespace)(input)?;
-    Ok(result)
+/// Parse parameter declaration: param WIDTH = 8
+pub fn parse_param(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("param")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0))(input)?;
+    let (input, value) = integer(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, Statement::Param { name, value }))
 }
 
-/// Parse entire category theory file
-pub fn parse_category_file(input: &str) -> IResult<&str, CategoryAST> {
-    let (input, statements) = separated_list1(
-        many1(line_ending),
-        parse_statement,
+/// Parse an interface field: data: 32
+fn interface_field(input: &str) -> IResult<&str, (String, usize)> {
+    separated_pair(identifier, tuple((space0, tag(":"), space0)), integer)(input)
+}
+
+/// Parse interface declaration: interface Bus { data: 32, valid: 1 }
+pub fn parse_interface(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("interface")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = pair(space0, tag("{"))(input)?;
+    let (input, fields) = separated_list1(
+        tag(","),
+        delimited(whitespace, interface_field, whitespace),
     )(input)?;
-    Ok((input, CategoryAST { statements }))
+    let (input, _) = tag("}")(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, Statement::Interface { name, fields }))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Parse a part select range, most significant bit first: [7:4]
+fn slice_range(input: &str) -> IResult<&str, (usize, usize)> {
+    verify(
+        delimited(
+            pair(tag("["), space0),
+            separated_pair(integer, tuple((space0, tag(":"), space0)), integer),
+            pair(space0, tag("]")),
+        ),
+        |(msb, lsb)| msb >= lsb,
+    )(input)
+}
 
-    #[test]
-    fn test_parse_object() {
-        assert_eq!(
-            parse_object("object A"),
-            Ok(("", Statement::Object("A".to_string())))
-        );
+/// Parse an operand in a morphism body: a literal, signal reference, part
+/// select, complement, concatenation, conditional or parenthesized expression
+fn expr_atom(input: &str) -> IResult<&str, Expr> {
+    alt((
+        conditional,
+        concatenation,
+        map(preceded(pair(tag("~"), space0), expr_atom), |operand| Expr::Not(Box::new(operand))),
+        map(map_res(digit1, |s: &str| s.parse::<u64>()), Expr::Literal),
+        map(pair(identifier, opt(slice_range)), |(name, range)| match range {
+            Some((msb, lsb)) => Expr::Slice { name, msb, lsb },
+            None => Expr::Ident(name),
+        }),
+        delimited(pair(tag("("), space0), expr, pair(space0, tag(")"))),
+    ))(input)
+}
+
+/// Parse a concatenation, the first part in the upper bits: {in[3:0], in[7:4]}
+fn concatenation(input: &str) -> IResult<&str, Expr> {
+    map(
+        delimited(
+            pair(tag("{"), space0),
+            separated_list1(tuple((space0, tag(","), space0)), expr),
+            pair(space0, tag("}")),
+        ),
+        Expr::Concat,
+    )(input)
+}
+
+/// Parse a conditional, whose last branch extends as far right as it can:
+/// if in > 10 then in - 10 else in
+fn conditional(input: &str) -> IResult<&str, Expr> {
+    let (input, _) = pair(tag("if"), space1)(input)?;
+    let (input, condition) = expr(input)?;
+    let (input, _) = tuple((space1, tag("then"), space1))(input)?;
+    let (input, then) = expr(input)?;
+    let (input, _) = tuple((space1, tag("else"), space1))(input)?;
+    let (input, otherwise) = expr(input)?;
+    Ok((input, Expr::If { condition: Box::new(condition), then: Box::new(then), otherwise: Box::new(otherwise) }))
+}
+
+/// Fold a chain of same-precedence operators into a left-associative tree
+fn fold_binary(first: Expr, rest: Vec<(BinOp, Expr)>) -> Expr {
+    rest.into_iter().fold(first, |lhs, (op, rhs)| Expr::Binary {
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+    })
+}
+
+/// Binary operators grouped by precedence, loosest first, as in Verilog
+const BINARY_LEVELS: &[&[BinOp]] = &[
+    &[BinOp::Or],
+    &[BinOp::Xor],
+    &[BinOp::And],
+    &[BinOp::Eq, BinOp::Ne],
+    // `<=` and `>=` go first so `<` and `>` don't take their first character
+    &[BinOp::Le, BinOp::Ge, BinOp::Lt, BinOp::Gt],
+    &[BinOp::Shl, BinOp::Shr],
+    &[BinOp::Add, BinOp::Sub],
+    &[BinOp::Mul],
+];
+
+/// Parse one of the operators of a precedence level
+fn binary_op<'a>(ops: &'static [BinOp]) -> impl FnMut(&'a str) -> IResult<&'a str, BinOp> {
+    move |input: &'a str| {
+        for op in ops {
+            if let Some(rest) = input.strip_prefix(op.symbol()) {
+                return Ok((rest, *op));
+            }
+        }
+        Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Tag)))
     }
+}
 
-    #[test]
-    fn test_parse_morphism() {
-        assert_eq!(
-            parse_morphism("morphism f: A -> B"),
-            Ok(("", Statement::Morphism {
-                name: "f".to_string(),
-                from: "A".to_string(),
-                to: "B".to_string(),
-            }))
-        );
+/// Parse a left-associative operator chain at one precedence level
+fn expr_level(input: &str, level: usize) -> IResult<&str, Expr> {
+    if level == BINARY_LEVELS.len() {
+        return expr_atom(input);
     }
+    let (input, first) = expr_level(input, level + 1)?;
+    let (input, rest) = many0(pair(
+        delimited(space0, binary_op(BINARY_LEVELS[level]), space0),
+        |input| expr_level(input, level + 1),
+    ))(input)?;
+    Ok((input, fold_binary(first, rest)))
+}
 
-    #[test]
-    fn test_parse_assert_commute() {
-        assert_eq!(
-            parse_assert_commute("assert commute: g ∘ f == h"),
-            Ok(("", Statement::AssertCommute {
-                lhs: vec!["g".to_string(), "f".to_string()],
-                rhs: vec!["h".to_string()],
-            }))
-        );
+/// Parse a morphism body expression: in * 3 + 1
+pub fn expr(input: &str) -> IResult<&str, Expr> {
+    expr_level(input, 0)
+}
+
+/// Parse a lane range and return its count: [0..8]
+fn lane_range(input: &str) -> IResult<&str, usize> {
+    map_res(
+        delimited(
+            tag("["),
+            separated_pair(integer, tag(".."), integer),
+            tag("]"),
+        ),
+        |(start, end)| {
+            if end > start {
+                Ok(end - start)
+            } else {
+                Err(format!("empty lane range {}..{}", start, end))
+            }
+        },
+    )(input)
+}
+
+/// Parse the arrow of a morphism: ->, or the Unicode → (U+2192)
+fn arrow(input: &str) -> IResult<&str, &str> {
+    alt((tag("->"), tag("→")))(input)
+}
+
+/// Parse the width a morphism expects of an endpoint, or of a wire: `: 8`
+fn endpoint_width(input: &str) -> IResult<&str, WidthExpr> {
+    preceded(tuple((space0, tag(":"), space0)), width_expr)(input)
+}
+
+/// Parse one binding of a body block: let t = in >> 2
+fn binding(input: &str) -> IResult<&str, (String, Expr)> {
+    let (input, _) = pair(tag("let"), space1)(input)?;
+    let (input, name) = plain_identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0))(input)?;
+    let (input, value) = expr(input)?;
+    Ok((input, (name, value)))
+}
+
+/// Parse the body of a morphism, inline or as a block that may span
+/// lines and bind intermediate values before `out`: = in * 3 + 1, or
+/// { let t = in >> 2; out = t + in }
+fn morphism_body(input: &str) -> IResult<&str, (Bindings, Expr)> {
+    alt((
+        map(preceded(tuple((space0, tag("="), space0)), expr), |body| (Vec::new(), body)),
+        delimited(
+            tuple((space0, tag("{"), whitespace)),
+            pair(
+                many0(terminated(binding, pair(statement_separator, whitespace))),
+                preceded(tuple((tag("out"), space0, tag("="), space0)), expr),
+            ),
+            tuple((opt(pair(trailing_space, tag(";"))), whitespace, tag("}"))),
+        ),
+    ))(input)
+}
+
+/// Parse one attribute: @keep, @pipeline(2), @latency(2, cycles)
+fn attribute(input: &str) -> IResult<&str, Attribute> {
+    let arg = alt((map(digit1, |digits: &str| digits.to_string()), plain_identifier));
+    let args = delimited(pair(tag("("), space0), separated_list1(tuple((space0, tag(","), space0)), arg), pair(space0, tag(")")));
+    let (input, name) = preceded(tag("@"), plain_identifier)(input)?;
+    let (input, args) = opt(args)(input)?;
+    Ok((input, Attribute { name, args: args.unwrap_or_default() }))
+}
+
+/// Parse the attributes leading a declaration, each followed by a space
+fn attributes(input: &str) -> IResult<&str, Vec<Attribute>> {
+    many0(terminated(attribute, space1))(input)
+}
+
+/// Parse morphism declaration: morphism f: A -> B, optionally registered,
+/// with lanes, generic over a width, with a body or an extern file, a
+/// handshake and a guard:
+/// seq morphism lane[0..8]: A -> B = in + 1 @handshake @ifdef SIMD
+/// morphism f<W>: A<W> -> B<W>
+/// A Kleisli morphism marks both endpoints: morphism f: maybe A -> maybe B
+/// Attributes for backends go first: @pipeline(2) morphism f: A -> B
+pub fn parse_morphism(input: &str) -> IResult<&str, Statement> {
+    let (input, attributes) = attributes(input)?;
+    let (input, registered) = map(opt(pair(tag("seq"), space1)), |seq| seq.is_some())(input)?;
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, lanes) = opt(lane_range)(input)?;
+    let (input, generic) = opt(delimited(tag("<"), plain_identifier, tag(">")))(input)?;
+    // Both endpoints of a generic morphism take its width parameter
+    let endpoint = |input| match &generic {
+        Some(generic) => map(delimited(tag("<"), tag(generic.as_str()), tag(">")), |_| None)(input),
+        None => opt(endpoint_width)(input),
+    };
+    let (input, _) = tag(":")(input)?;
+    let (input, _) = space0(input)?;
+    // `maybe` is only a modifier when an object name follows it
+    let modifier = || terminated(tag("maybe"), pair(space1, peek(identifier)));
+    let (input, maybe) = map(opt(modifier()), |modifier| modifier.is_some())(input)?;
+    let (input, from) = identifier(input)?;
+    let (input, from_width) = endpoint(input)?;
+    let (input, _) = space0(input)?;
+    let (input, _) = arrow(input)?;
+    let (input, _) = space0(input)?;
+    let (input, _) = cond(maybe, modifier())(input)?;
+    let (input, to) = identifier(input)?;
+    let (input, to_width) = endpoint(input)?;
+    let (input, body) = opt(morphism_body)(input)?;
+    let (bindings, body) = match body {
+        Some((bindings, body)) => (bindings, Some(body)),
+        None => (Vec::new(), None),
+    };
+    // A black box has no body to generate
+    let (input, external) = map(cond(body.is_none(), opt(external)), Option::flatten)(input)?;
+    let (input, handshake) = map(opt(pair(space0, tag("@handshake"))), |tag| tag.is_some())(input)?;
+    let (input, guard) = opt(guard)(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, Statement::Morphism { name, from, to, from_width, to_width, body, bindings, lanes, registered, guard, external, handshake, maybe, generic, attributes }))
+}
+
+/// Parse case analysis out of a coproduct: morphism h: C -> D = [f, g]
+pub fn parse_case(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = pair(tag(":"), space0)(input)?;
+    let (input, from) = identifier(input)?;
+    let (input, _) = delimited(space0, arrow, space0)(input)?;
+    let (input, to) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0, tag("["), space0))(input)?;
+    let (input, branches) = separated_list1(delimited(space0, tag(","), space0), identifier)(input)?;
+    let (input, _) = pair(space0, tag("]"))(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, Statement::Case { name, from, to, branches }))
+}
+
+/// Parse a morphism given by a truth table, one row per line or separated
+/// by commas: morphism dec: A -> B table { 00 -> 01, 1? -> 10 }
+/// Members of an enumerated endpoint stand for their encoding: IDLE -> RUN
+pub fn parse_table(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = pair(tag(":"), space0)(input)?;
+    let (input, from) = identifier(input)?;
+    let (input, _) = delimited(space0, arrow, space0)(input)?;
+    let (input, to) = identifier(input)?;
+    let (input, _) = tuple((space1, tag("table"), space0, tag("{"), whitespace))(input)?;
+    let separator = alt((recognize(tuple((space0, tag(","), whitespace))), recognize(pair(line_breaks, space0))));
+    let bits = |digits: &'static str| map(take_while1(move |c: char| digits.contains(c)), |s: &str| s.to_string());
+    let row = separated_pair(alt((bits("01?"), plain_identifier)), delimited(space0, arrow, space0), alt((bits("01"), plain_identifier)));
+    let (input, rows) = separated_list1(separator, row)(input)?;
+    let (input, _) = tuple((whitespace, tag("}"), trailing_space))(input)?;
+    Ok((input, Statement::Table { name, from, to, rows }))
+}
+
+/// Parse the text between a pair of braces, which may nest: { {a, b} }
+fn braced(input: &str) -> IResult<&str, &str> {
+    let (text, _) = tag("{")(input)?;
+    let mut depth = 0usize;
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Ok((&text[i + 1..], &text[..i])),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::TakeUntil)))
+}
+
+/// Parse a morphism written directly in Verilog, naming its ports `in` and
+/// `out`: morphism f: A -> B verilog { assign out = ^in; }
+pub fn parse_verilog(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = pair(tag(":"), space0)(input)?;
+    let (input, from) = identifier(input)?;
+    let (input, _) = delimited(space0, arrow, space0)(input)?;
+    let (input, to) = identifier(input)?;
+    let (input, _) = tuple((space1, tag("verilog"), space0))(input)?;
+    let (input, text) = verify(map(braced, str::trim), |text: &str| !text.is_empty())(input)?;
+    let (input, _) = trailing_space(input)?;
+    Ok((input, Statement::Verilog { name, from, to, text: text.to_string() }))
+}
+
+/// Parse a morphism looking its result up in a ROM loaded from a hex file:
+/// morphism lut: Addr -> Data rom "table.hex"
+pub fn parse_rom(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = pair(tag(":"), space0)(input)?;
+    let (input, from) = identifier(input)?;
+    let (input, _) = delimited(space0, arrow, space0)(input)?;
+    let (input, to) = identifier(input)?;
+    let (input, _) = tuple((space1, tag("rom"), space1))(input)?;
+    let (input, path) = map(delimited(tag("\""), is_not("\"\r\n"), tag("\"")), |s: &str| s.to_string())(input)?;
+    let (input, _) = trailing_space(input)?;
+    Ok((input, Statement::Rom { name, from, to, path }))
+}
+
+/// Parse a morphism from the built-in library: morphism s = std.add(A, B) -> C
+pub fn parse_library(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0, tag("std.")))(input)?;
+    let (input, element) = plain_identifier(input)?;
+    let (input, _) = tuple((space0, tag("("), space0))(input)?;
+    let (input, from) = separated_list1(tuple((space0, tag(","), space0)), identifier)(input)?;
+    let (input, _) = tuple((space0, tag(")"), space0, arrow, space0))(input)?;
+    let (input, to) = identifier(input)?;
+    let (input, _) = trailing_space(input)?;
+    Ok((input, Statement::Library { name, element, from, to }))
+}
+
+/// Parse a reduction of an object family by an associative library
+/// morphism into a balanced tree of library morphisms:
+/// morphism sum = reduce(std.add, A[0..7]) -> S
+pub fn parse_reduce(input: &str) -> IResult<&str, Vec<Statement>> {
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0, tag("reduce"), space0, tag("("), space0, tag("std.")))(input)?;
+    let (input, element) = verify(plain_identifier, |element: &str| STD_REDUCTIONS.contains(&element))(input)?;
+    let (input, _) = tuple((space0, tag(","), space0))(input)?;
+    let (input, family) = plain_identifier(input)?;
+    let (input, (start, end)) = verify(
+        delimited(tag("["), separated_pair(integer, tag(".."), integer), tag("]")),
+        |(start, end)| start < end,
+    )(input)?;
+    let (input, _) = tuple((space0, tag(")"), space0, arrow, space0))(input)?;
+    let (input, to) = identifier(input)?;
+    let (input, _) = trailing_space(input)?;
+    let members = (start..=end).map(|index| family_member(&family, index)).collect();
+    Ok((input, reduction_tree(&name, &element, members, &to)))
+}
+
+/// Combine `operands` pairwise, level by level, an odd one out moving up
+/// unchanged. Step `i` of level `l` is the morphism `<name>_<l>_<i>`
+/// driving the wire `<name>_<l>_<i>_out`, sized to keep its full result;
+/// the last step is `name` itself and drives `to`
+fn reduction_tree(name: &str, element: &str, mut operands: Vec<String>, to: &str) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    let mut level = 0;
+    while operands.len() > 1 {
+        let mut results = Vec::new();
+        for (index, pair) in operands.chunks(2).enumerate() {
+            let [a, b] = pair else {
+                results.push(pair[0].clone());
+                continue;
+            };
+            let from = vec![a.clone(), b.clone()];
+            if operands.len() == 2 {
+                statements.push(Statement::Library { name: name.to_string(), element: element.to_string(), from, to: to.to_string() });
+                break;
+            }
+            let step = format!("{}_{}_{}", name, level, index);
+            let wire = format!("{}_out", step);
+            let (a, b) = (Box::new(WidthExpr::Ref(a.clone())), Box::new(WidthExpr::Ref(b.clone())));
+            let width = match element {
+                "add" => WidthExpr::Add(Box::new(WidthExpr::Max(a, b)), Box::new(WidthExpr::Const(1))),
+                "mul" => WidthExpr::Add(a, b),
+                _ => WidthExpr::Max(a, b),
+            };
+            statements.push(Statement::Wire { name: wire.clone(), width: Some(width) });
+            statements.push(Statement::Library { name: step, element: element.to_string(), from, to: wire.clone() });
+            results.push(wire);
+        }
+        operands = results;
+        level += 1;
+    }
+    statements
+}
+
+/// Parse morphism reading several objects, optionally registered, whose
+/// body names them: morphism add: (A, B) -> C = A + B
+pub fn parse_multi_input(input: &str) -> IResult<&str, Statement> {
+    let (input, registered) = map(opt(pair(tag("seq"), space1)), |seq| seq.is_some())(input)?;
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((tag(":"), space0, tag("("), space0))(input)?;
+    let separator = || tuple((space0, tag(","), space0));
+    let (input, first) = identifier(input)?;
+    let (input, rest) = many1(preceded(separator(), identifier))(input)?;
+    let (input, _) = tuple((space0, tag(")"), space0, arrow, space0))(input)?;
+    let (input, to) = identifier(input)?;
+    // The body reads the inputs by name, so there's nothing to bind
+    let (input, body) = opt(map(verify(morphism_body, |(bindings, _)| bindings.is_empty()), |(_, body)| body))(input)?;
+    let (input, _) = space0(input)?;
+    let mut from = vec![first];
+    from.extend(rest);
+    Ok((input, Statement::MultiInput { name, from, to, body, registered }))
+}
+
+/// Parse constant morphism from the unit object, whose body is a single
+/// literal: morphism zero: I -> A = 8'h00
+pub fn parse_constant(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = pair(tag(":"), space0)(input)?;
+    let (input, _) = verify(identifier, |from: &str| from == UNIT_OBJECT)(input)?;
+    let (input, _) = delimited(space0, arrow, space0)(input)?;
+    let (input, to) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0))(input)?;
+    // Anything but a comment or the end of a statement or block after the
+    // literal makes it a body
+    let (input, value) = terminated(verilog_number, not(pair(space0, none_of("\r\n/;}"))))(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, Statement::Constant { name, to, value }))
+}
+
+/// Parse tensor product of morphisms: morphism p = f ⊗ g
+pub fn parse_tensor(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0))(input)?;
+    let (input, first) = identifier(input)?;
+    let (input, rest) = many1(preceded(delimited(space0, tag("⊗"), space0), identifier))(input)?;
+    let (input, _) = space0(input)?;
+    let mut factors = vec![first];
+    factors.extend(rest);
+    Ok((input, Statement::Tensor { name, factors }))
+}
+
+/// Parse feedback around a morphism: morphism fsm = trace(step)
+pub fn parse_trace(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0, tag("trace"), space0, tag("("), space0))(input)?;
+    let (input, step) = identifier(input)?;
+    let (input, _) = tuple((space0, tag(")"), space0))(input)?;
+    Ok((input, Statement::Trace { name, step }))
+}
+
+/// Parse braiding of a two-component product: morphism s = swap(A, B)
+pub fn parse_swap(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0, tag("swap"), space0, tag("("), space0))(input)?;
+    let (input, first) = identifier(input)?;
+    let (input, _) = tuple((space0, tag(","), space0))(input)?;
+    let (input, second) = identifier(input)?;
+    let (input, _) = tuple((space0, tag(")"), space0))(input)?;
+    Ok((input, Statement::Swap { name, first, second }))
+}
+
+/// Parse projection of a product onto a component, counted from 1:
+/// morphism p = pi1(C)
+pub fn parse_projection(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0, tag("pi")))(input)?;
+    let (input, index) = verify(integer, |index| *index > 0)(input)?;
+    let (input, _) = tuple((space0, tag("("), space0))(input)?;
+    let (input, product) = identifier(input)?;
+    let (input, _) = tuple((space0, tag(")"), space0))(input)?;
+    Ok((input, Statement::Projection { name, product, index }))
+}
+
+/// Parse injection into a two-component coproduct: morphism i = inl(C)
+pub fn parse_injection(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0))(input)?;
+    let (input, right) = alt((value(false, tag("inl")), value(true, tag("inr"))))(input)?;
+    let (input, _) = tuple((space0, tag("("), space0))(input)?;
+    let (input, coproduct) = identifier(input)?;
+    let (input, _) = tuple((space0, tag(")"), space0))(input)?;
+    Ok((input, Statement::Injection { name, coproduct, right }))
+}
+
+/// Parse duplication of an object into its square: morphism d = dup(A)
+pub fn parse_dup(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0, tag("dup"), space0, tag("("), space0))(input)?;
+    let (input, object) = identifier(input)?;
+    let (input, _) = tuple((space0, tag(")"), space0))(input)?;
+    Ok((input, Statement::Dup { name, object }))
+}
+
+/// Parse functor declaration, one mapping per line or separated by commas:
+/// functor F: alu -> core { A => X, inc => step }
+pub fn parse_functor(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("functor")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = pair(tag(":"), space0)(input)?;
+    let (input, from) = identifier(input)?;
+    let (input, _) = delimited(space0, arrow, space0)(input)?;
+    let (input, to) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("{"), whitespace))(input)?;
+    let separator = alt((recognize(tuple((space0, tag(","), whitespace))), recognize(pair(line_breaks, space0))));
+    let entry = separated_pair(identifier, delimited(space0, tag("=>"), space0), identifier);
+    let (input, mapping) = separated_list0(separator, entry)(input)?;
+    let (input, _) = tuple((whitespace, tag("}"), space0))(input)?;
+    Ok((input, Statement::Functor { name, from, to, mapping }))
+}
+
+/// Parse natural transformation declaration, with optional component
+/// bodies: nat eta: F => G { X = in + 1 }
+pub fn parse_nat(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("nat")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = pair(tag(":"), space0)(input)?;
+    let (input, from) = identifier(input)?;
+    let (input, _) = delimited(space0, tag("=>"), space0)(input)?;
+    let (input, to) = identifier(input)?;
+    let separator = alt((recognize(tuple((space0, tag(","), whitespace))), recognize(pair(line_breaks, space0))));
+    let component = separated_pair(identifier, delimited(space0, tag("="), space0), expr);
+    let (input, components) = opt(delimited(
+        tuple((space0, tag("{"), whitespace)),
+        separated_list0(separator, component),
+        tuple((whitespace, tag("}"))),
+    ))(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, Statement::Nat { name, from, to, components: components.unwrap_or_default() }))
+}
+
+/// Parse import of another file, optionally under a section prefix:
+/// import "lib/adders.cat" as adders
+pub fn parse_import(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("import")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, path) = map(delimited(tag("\""), is_not("\"\r\n"), tag("\"")), |s: &str| s.to_string())(input)?;
+    let (input, alias) = opt(preceded(tuple((space1, tag("as"), space1)), plain_identifier))(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, Statement::Import { path, alias }))
+}
+
+/// Parse a template, whose body is kept as text until a use of it fills in
+/// the parameters: template pipeline(X, n) { object R[0] : X }
+pub fn parse_template(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("template")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = plain_identifier(input)?;
+    let (input, params) = delimited(
+        tuple((space0, tag("("), space0)),
+        separated_list0(tuple((space0, tag(","), space0)), plain_identifier),
+        pair(space0, tag(")")),
+    )(input)?;
+    let (input, _) = space0(input)?;
+    let (input, body) = verify(map(braced, str::trim), |body: &str| !body.is_empty())(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, Statement::Template { name, params, body: body.to_string() }))
+}
+
+/// Parse a use of a template with names and integers as its arguments:
+/// use pipeline(A, 4) as p1
+pub fn parse_use(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("use")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, template) = identifier(input)?;
+    let (input, args) = delimited(
+        tuple((space0, tag("("), space0)),
+        separated_list0(tuple((space0, tag(","), space0)), alt((map(digit1, String::from), identifier))),
+        pair(space0, tag(")")),
+    )(input)?;
+    let (input, alias) = opt(preceded(tuple((space1, tag("as"), space1)), plain_identifier))(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, Statement::Use { template, args, alias }))
+}
+
+/// Parse the body of each target of a fan-out: (B: in[3:0], C: in[7:4])
+fn target_bodies(input: &str) -> IResult<&str, Vec<(String, Expr)>> {
+    delimited(
+        pair(tag("("), space0),
+        separated_list1(
+            tuple((space0, tag(","), space0)),
+            separated_pair(identifier, tuple((space0, tag(":"), space0)), expr),
+        ),
+        pair(space0, tag(")")),
+    )(input)
+}
+
+/// Parse a morphism with several targets, each computed by its own body,
+/// into one morphism per target named `<name>_<target>`:
+/// morphism split: A -> (B, C) = (B: in[3:0], C: in[7:4])
+pub fn parse_fanout(input: &str) -> IResult<&str, Vec<Statement>> {
+    let (input, attributes) = attributes(input)?;
+    let (input, registered) = map(opt(pair(tag("seq"), space1)), |seq| seq.is_some())(input)?;
+    let (input, _) = tag("morphism")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, lanes) = opt(lane_range)(input)?;
+    let (input, _) = pair(tag(":"), space0)(input)?;
+    let (input, from) = identifier(input)?;
+    let (input, from_width) = opt(endpoint_width)(input)?;
+    let (input, _) = tuple((space0, arrow, space0, tag("("), space0))(input)?;
+    let (input, targets) = separated_list1(tuple((space0, tag(","), space0)), identifier)(input)?;
+    let (input, _) = tuple((space0, tag(")"), space0, tag("="), space0))(input)?;
+    let body_start = input;
+    let (input, mut bodies) = target_bodies(input)?;
+    let (input, guard) = opt(guard)(input)?;
+    let (input, _) = space0(input)?;
+
+    // Every target needs exactly one body, and every body a target
+    let mut statements = Vec::new();
+    for to in targets {
+        let Some(index) = bodies.iter().position(|(target, _)| *target == to) else {
+            return Err(nom::Err::Failure(nom::error::Error::new(body_start, nom::error::ErrorKind::Verify)));
+        };
+        let (_, body) = bodies.remove(index);
+        statements.push(Statement::Morphism {
+            name: format!("{}_{}", name, to),
+            from: from.clone(),
+            to,
+            from_width: from_width.clone(),
+            to_width: None,
+            body: Some(body),
+            bindings: Vec::new(),
+            lanes,
+            registered,
+            guard: guard.clone(),
+            external: None,
+            handshake: false,
+            maybe: false,
+            generic: None,
+            attributes: attributes.clone(),
+        });
+    }
+    if !bodies.is_empty() {
+        return Err(nom::Err::Failure(nom::error::Error::new(body_start, nom::error::ErrorKind::Verify)));
+    }
+    Ok((input, statements))
+}
+
+/// Parse isomorphism declaration, optionally with the bodies of both
+/// directions: iso f: A <-> B = in + 1, in - 1
+pub fn parse_iso(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("iso")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = pair(tag(":"), space0)(input)?;
+    let (input, from) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("<->"), space0))(input)?;
+    let (input, to) = identifier(input)?;
+    let (input, bodies) = opt(preceded(
+        tuple((space0, tag("="), space0)),
+        separated_pair(expr, tuple((space0, tag(","), space0)), expr),
+    ))(input)?;
+    let (input, _) = space0(input)?;
+    let (body, inverse_body) = bodies.map_or((None, None), |(body, inverse)| (Some(body), Some(inverse)));
+    let inverse = format!("{}_inv", name);
+    Ok((input, Statement::Iso { name, inverse, from, to, body, inverse_body }))
+}
+
+/// Parse composition operator: ∘, or its ASCII aliases `.` and `o`, the
+/// latter set apart by spaces from the names around it
+fn parse_composition_op(input: &str) -> IResult<&str, &str> {
+    alt((
+        delimited(space0, alt((tag("∘"), tag("."))), space0),
+        delimited(space1, tag("o"), space1),
+    ))(input)
+}
+
+/// Parse diagrammatic composition operator: `f ; g` applies f first
+fn parse_diagrammatic_op(input: &str) -> IResult<&str, &str> {
+    delimited(space0, tag(";"), space0)(input)
+}
+
+/// Parse a composition, outermost morphism first: `g ∘ f`, `g . f` and
+/// `f ; g` all compose f before g. Operators of both orders can't be mixed
+/// at one level of parentheses, and an unparenthesised chain groups to the
+/// right: `h ∘ g ∘ f` is `h ∘ (g ∘ f)`
+fn composition(input: &str) -> IResult<&str, Composition> {
+    let compose = |outer, inner| Composition::Compose(Box::new(outer), Box::new(inner));
+    alt((
+        map(
+            pair(composition_term, many1(preceded(parse_diagrammatic_op, composition_term))),
+            move |(first, rest)| rest.into_iter().fold(first, |inner, outer| compose(outer, inner)),
+        ),
+        map(separated_list1(parse_composition_op, composition_term), move |terms| {
+            terms.into_iter().rev().reduce(|inner, outer| compose(outer, inner)).unwrap()
+        }),
+    ))(input)
+}
+
+/// Parse a morphism of a composition, or a parenthesised composition
+fn composition_term(input: &str) -> IResult<&str, Composition> {
+    alt((
+        map(chain_identifier, Composition::Morphism),
+        delimited(pair(tag("("), space0), composition, pair(space0, tag(")"))),
+    ))(input)
+}
+
+/// Parse composition operator with an optional junction width: ∘[4]
+fn junction_op(input: &str) -> IResult<&str, Option<usize>> {
+    let width = || opt(delimited(tag("["), verify(integer, |width| *width > 0), tag("]")));
+    alt((
+        delimited(space0, preceded(alt((tag("∘"), tag("."))), width()), space0),
+        delimited(space1, preceded(tag("o"), width()), space1),
+    ))(input)
+}
+
+/// Parse composite morphism definition, outermost morphism first, with
+/// optional junction widths: define h = g ∘[4] f, or let h = g ∘ f
+pub fn parse_define(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = alt((tag("define"), tag("let")))(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("="), space0))(input)?;
+    // `g.f` is a whole chain on its own
+    let (input, (outermost, rest)) = verify(
+        pair(chain_identifier, many0(pair(junction_op, chain_identifier))),
+        |(outermost, rest): &(String, Vec<_>)| !rest.is_empty() || outermost.contains('.'),
+    )(input)?;
+    let (input, _) = space0(input)?;
+    let (junction_widths, inner): (Vec<Option<usize>>, Vec<String>) = rest.into_iter().unzip();
+    let mut chain = vec![outermost];
+    chain.extend(inner);
+    Ok((input, Statement::Define { name, chain, junction_widths }))
+}
+
+/// Parse a test vector of a morphism: test f: in 8'd5 => out 8'd6
+pub fn parse_test(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("test")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, morphism) = identifier(input)?;
+    let (input, _) = tuple((tag(":"), space0, tag("in"), space1))(input)?;
+    let (input, value) = verilog_number(input)?;
+    let (input, _) = tuple((space0, tag("=>"), space0, tag("out"), space1))(input)?;
+    let (input, expected) = verilog_number(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, Statement::Test { morphism, input: value, expected }))
+}
+
+/// Parse identity declaration: id A, or morphism i := identity A
+pub fn parse_identity(input: &str) -> IResult<&str, Statement> {
+    alt((
+        map(preceded(pair(tag("id"), space1), identifier), |object| Statement::Identity {
+            name: identity_name(&object),
+            object,
+        }),
+        map(
+            pair(
+                preceded(pair(tag("morphism"), space1), identifier),
+                preceded(tuple((space0, tag(":="), space0, tag("identity"), space1)), identifier),
+            ),
+            |(name, object)| Statement::Identity { name, object },
+        ),
+    ))(input)
+}
+
+/// Parse commutativity assertion, either side of which may group its
+/// composition with parentheses: assert commute: g ∘ f == h
+pub fn parse_assert_commute(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("assert commute:")(input)?;
+    let (input, _) = space0(input)?;
+    let (input, lhs) = composition(input)?;
+    let (input, _) = space0(input)?;
+    let (input, _) = tag("==")(input)?;
+    let (input, _) = space0(input)?;
+    let (input, rhs) = composition(input)?;
+    let (input, _) = space0(input)?;
+    Ok((input, Statement::AssertCommute { lhs, rhs }))
+}
+
+/// Parse equality assertion between parallel morphisms: assert equal: f == g
+pub fn parse_assert_equal(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = tag("assert equal:")(input)?;
+    let (input, _) = space0(input)?;
+    let (input, lhs) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("=="), space0))(input)?;
+    let (input, rhs) = identifier(input)?;
+    let (input, _) = trailing_space(input)?;
+    Ok((input, Statement::AssertEqual { lhs, rhs }))
+}
+
+/// Parse mono or epi assertion: assert mono f, assert epi g
+pub fn parse_assert_kind(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = pair(tag("assert"), space1)(input)?;
+    let (input, kind) = alt((value(MorphismKind::Mono, tag("mono")), value(MorphismKind::Epi, tag("epi"))))(input)?;
+    let (input, _) = space1(input)?;
+    let (input, morphism) = identifier(input)?;
+    let (input, _) = trailing_space(input)?;
+    Ok((input, Statement::AssertKind { morphism, kind }))
+}
+
+/// Parse a single statement line
+pub fn parse_statement(input: &str) -> IResult<&str, Statement> {
+    let (input, _) = opt(whitespace)(input)?;
+    let (input, stmt) = alt((parse_product, parse_coproduct, parse_object, parse_identity, parse_case, parse_constant, parse_multi_input, parse_trace, parse_tensor, parse_morphism, parse_wire, parse_interface, parse_assert_commute, parse_param, parse_iso, parse_define, parse_test, parse_functor, parse_nat, parse_import, parse_clock))(input)?;
+    let (input, _) = trailing_space(input)?;
+    Ok((input, stmt))
+}
+
+/// Parse a block whose declarations are prefixed with its name, one
+/// statement per line: section alu { object A }
+pub fn parse_section(input: &str) -> IResult<&str, Vec<Statement>> {
+    let (input, _) = tag("section")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("{")))(input)?;
+    let (input, statements) = statement_list(input)?;
+    let (input, _) = tuple((whitespace, tag("}"), space0))(input)?;
+    Ok((input, namespaced(statements, &name)))
+}
+
+/// Parse a category block: its declarations are prefixed like a section's,
+/// after a statement recording the block: category Alu { object A }
+pub fn parse_category(input: &str) -> IResult<&str, Vec<Statement>> {
+    let (input, _) = tag("category")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, tag("{")))(input)?;
+    let (input, statements) = statement_list(input)?;
+    let (input, _) = tuple((whitespace, tag("}"), space0))(input)?;
+    let mut block = vec![Statement::Category { name: name.clone() }];
+    block.extend(namespaced(statements, &name));
+    Ok((input, block))
+}
+
+/// Parse a loop over an inclusive index range, whose body is repeated once
+/// per index with the names indexed by the loop variable filled in:
+/// for i in 0..7 { morphism f[i]: A[i] -> B[i] }
+pub fn parse_for(input: &str) -> IResult<&str, Vec<Statement>> {
+    let (input, _) = tag("for")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, variable) = plain_identifier(input)?;
+    let (input, _) = tuple((space1, tag("in"), space1))(input)?;
+    let (input, (start, end)) = verify(separated_pair(integer, tag(".."), integer), |(start, end)| start <= end)(input)?;
+    let (input, _) = tuple((space0, tag("{")))(input)?;
+    let (input, body) = statement_list(input)?;
+    let (input, _) = tuple((whitespace, tag("}"), space0))(input)?;
+    Ok((input, (start..=end).flat_map(|index| unrolled(&body, &variable, index)).collect()))
+}
+
+/// Parse one line of the file, which may declare several objects at once,
+/// or a whole section, category or loop
+pub fn parse_statements(input: &str) -> IResult<&str, Vec<Statement>> {
+    let (input, _) = opt(whitespace)(input)?;
+    alt((parse_section, parse_category, parse_for, map(parse_product, |stmt| vec![stmt]), map(parse_coproduct, |stmt| vec![stmt]), map(alt((parse_enum, parse_memory)), |stmt| vec![stmt]), map(parse_family, |stmt| vec![stmt]), parse_object_list, parse_fanout, map(parse_table, |stmt| vec![stmt]), map(alt((parse_verilog, parse_rom)), |stmt| vec![stmt]), alt((parse_reduce, map(parse_library, |stmt| vec![stmt]))), map(parse_swap, |stmt| vec![stmt]), map(parse_dup, |stmt| vec![stmt]), map(parse_projection, |stmt| vec![stmt]), map(parse_injection, |stmt| vec![stmt]), map(alt((parse_top, parse_template, parse_use)), |stmt| vec![stmt]), map(parse_pragma, |stmt| vec![stmt]), map(parse_assert_equal, |stmt| vec![stmt]), map(parse_assert_kind, |stmt| vec![stmt]), map(parse_statement, |stmt| vec![stmt])))(input)
+}
+
+/// Parse entire category theory file, whose statements are separated by
+/// line breaks or `;`
+pub fn parse_category_file(input: &str) -> IResult<&str, CategoryAST> {
+    let (input, statements) = separated_list1(statement_separator, parse_statements)(input)?;
+    let (input, _) = opt(pair(trailing_space, tag(";")))(input)?;
+    let (input, _) = opt(whitespace)(input)?;
+    Ok((input, CategoryAST { statements: statements.into_iter().flatten().collect() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_object() {
+        assert_eq!(
+            parse_object("object A"),
+            Ok(("", Statement::Object { name: "A".to_string(), width: None, signed: false, initial: None, active_low: false, fraction: None, domain: None }))
+        );
+    }
+
+    #[test]
+    fn test_parse_signed_object() {
+        assert_eq!(
+            parse_object("object D : signed 8"),
+            Ok(("", Statement::Object {
+                name: "D".to_string(),
+                width: Some(WidthExpr::Const(8)),
+                signed: true,
+                initial: None,
+                active_low: false,
+                fraction: None,
+                domain: None,
+            }))
+        );
+        assert_eq!(
+            parse_object("object D : signed"),
+            Ok(("", Statement::Object { name: "D".to_string(), width: None, signed: true, initial: None, active_low: false, fraction: None, domain: None }))
+        );
+        assert_eq!(
+            parse_object("object D : signed_bus"),
+            Ok(("", Statement::Object {
+                name: "D".to_string(),
+                width: Some(WidthExpr::Ref("signed_bus".to_string())),
+                signed: false,
+                initial: None,
+                active_low: false,
+                fraction: None,
+                domain: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_fixed_point_object() {
+        assert_eq!(
+            parse_object("object Q : signed fixed<8, 4>"),
+            Ok(("", Statement::Object {
+                name: "Q".to_string(),
+                width: Some(WidthExpr::Const(12)),
+                signed: true,
+                initial: None,
+                active_low: false,
+                fraction: Some(4),
+                domain: None,
+            }))
+        );
+        assert_eq!(parse_object("object Q : fixed<8,4> = 0").unwrap().1.to_source(), "object Q : fixed<8,4> = 0");
+        assert_eq!(parse_object("object Q : fixed").unwrap().1.to_source(), "object Q : fixed");
+    }
+
+    #[test]
+    fn test_parse_clock_domain() {
+        assert_eq!(parse_statement("clock clk_a"), Ok(("", Statement::Clock { name: "clk_a".to_string() })));
+        let (rest, stmt) = parse_object("object A : 8 active_low @clk_a = 8'h00").unwrap();
+        assert_eq!(rest, "");
+        assert!(matches!(&stmt, Statement::Object { domain: Some(domain), active_low: true, .. } if domain == "clk_a"));
+        assert_eq!(stmt.to_source(), "object A : 8 active_low @ clk_a = 8'h00");
+        let (_, objects) = parse_statements("object A, B @ clk_b").unwrap();
+        assert!(objects.iter().all(|stmt| matches!(stmt, Statement::Object { domain: Some(domain), .. } if domain == "clk_b")));
+    }
+
+    #[test]
+    fn test_parse_initial_value() {
+        assert_eq!(
+            parse_object("object R : 8 = 8'h3C"),
+            Ok(("", Statement::Object {
+                name: "R".to_string(),
+                width: Some(WidthExpr::Const(8)),
+                signed: false,
+                initial: Some("8'h3C".to_string()),
+                active_low: false,
+                fraction: None,
+                domain: None,
+            }))
+        );
+        assert_eq!(parse_object("object R = 60").unwrap().1.to_source(), "object R = 60");
+        assert_eq!(parse_object("object R = 4'sb1x0z").unwrap().0, "");
+        assert_ne!(parse_object("object R = 8'q1").unwrap().0, "");
+    }
+
+    #[test]
+    fn test_parse_wire() {
+        assert_eq!(
+            parse_statement("wire mid : A + 1"),
+            Ok(("", Statement::Wire {
+                name: "mid".to_string(),
+                width: Some(WidthExpr::Add(
+                    Box::new(WidthExpr::Ref("A".to_string())),
+                    Box::new(WidthExpr::Const(1)),
+                )),
+            }))
+        );
+        assert_eq!(
+            parse_statement("wire mid"),
+            Ok(("", Statement::Wire { name: "mid".to_string(), width: None }))
+        );
+    }
+
+    #[test]
+    fn test_parse_active_low() {
+        assert_eq!(
+            parse_object("object rst_n : 1 active_low"),
+            Ok(("", Statement::Object {
+                name: "rst_n".to_string(),
+                width: Some(WidthExpr::Const(1)),
+                signed: false,
+                initial: None,
+                active_low: true,
+                fraction: None,
+                domain: None,
+            }))
+        );
+        let (rest, stmt) = parse_object("object en active_low = 0").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(stmt.to_source(), "object en active_low = 0");
+        assert_eq!(parse_object("object en : signed active_low").unwrap().1.to_source(), "object en : signed active_low");
+        assert_eq!(parse_object("object en active_lowish").unwrap().0, "active_lowish");
+    }
+
+    #[test]
+    fn test_parse_param() {
+        assert_eq!(
+            parse_statement("param WIDTH = 8"),
+            Ok(("", Statement::Param { name: "WIDTH".to_string(), value: 8 }))
+        );
+        let (rest, ast) = parse_category_file("param WIDTH = 8\nobject A : WIDTH\n").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(ast.to_source(), "param WIDTH = 8\nobject A : WIDTH\n");
+        assert!(parse_param("param WIDTH").is_err());
+    }
+
+    #[test]
+    fn test_parse_section() {
+        let source = "object X : 4\nsection alu {\n    object A : X\n    object B : A + 1\n\n    morphism inc: A -> B = in + 1\n    section sub { object A }\n    assert commute: inc == inc\n}\nmorphism f: X -> alu_A\n";
+        let (rest, ast) = parse_category_file(source).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            ast.to_source(),
+            "object X : 4\nobject alu_A : X\nobject alu_B : alu_A + 1\nmorphism alu_inc: alu_A -> alu_B = in + 1\nobject alu_sub_A\nassert commute: alu_inc == alu_inc\nmorphism f: X -> alu_A\n"
+        );
+        assert_eq!(parse_statements("section empty { }"), Ok(("", vec![])));
+        assert!(parse_section("section alu { object A").is_err());
+    }
+
+    #[test]
+    fn test_parse_fanout() {
+        let (rest, stmts) = parse_statements("morphism split: A -> (B, C) = (C: in[7:4], B: in[3:0] + 1)").unwrap();
+        assert_eq!(rest, "");
+        let source: Vec<String> = stmts.iter().map(Statement::to_source).collect();
+        assert_eq!(source, ["morphism split_B: A -> B = in[3:0] + 1", "morphism split_C: A -> C = in[7:4]"]);
+
+        // Every target needs a body, and only targets may have one
+        assert!(parse_statements("morphism split: A -> (B, C) = (B: in[3:0])").is_err());
+        assert!(parse_statements("morphism split: A -> (B, C) = (B: in, C: in, D: in)").is_err());
+        assert!(expr("in[3:4]").map_or(true, |(rest, _)| !rest.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_iso() {
+        let (rest, stmt) = parse_statement("iso f: A <-> B = in + 1, in - 1").unwrap();
+        assert_eq!(rest, "");
+        let Statement::Iso { ref inverse, ref inverse_body, .. } = stmt else {
+            panic!("expected an iso, got {:?}", stmt);
+        };
+        assert_eq!(inverse, "f_inv");
+        assert_eq!(inverse_body.as_ref().map(Expr::to_source).as_deref(), Some("in - 1"));
+        assert_eq!(stmt.to_source(), "iso f: A <-> B = in + 1, in - 1");
+        assert_eq!(parse_statement("iso g: A <-> B").unwrap().1.to_source(), "iso g: A <-> B");
+    }
+
+    #[test]
+    fn test_parse_extern_morphism() {
+        let (rest, stmt) = parse_statement("morphism ext: A -> B extern \"rtl/ext_module.v\" @ifdef FAST").unwrap();
+        assert_eq!(rest, "");
+        let Statement::Morphism { ref body, ref external, ref guard, .. } = stmt else {
+            panic!("expected a morphism, got {:?}", stmt);
+        };
+        assert_eq!((body, external.as_deref(), guard.as_deref()), (&None, Some("rtl/ext_module.v"), Some("FAST")));
+        assert_eq!(stmt.to_source(), "morphism ext: A -> B extern \"rtl/ext_module.v\" @ifdef FAST");
+
+        // A morphism is either generated from a body or a black box
+        assert_ne!(parse_statement("morphism ext: A -> B = in extern \"ext.v\"").unwrap().0, "");
+    }
+
+    #[test]
+    fn test_parse_unicode_arrow() {
+        assert_eq!(parse_statement("morphism f: A → B = in + 1"), parse_statement("morphism f: A -> B = in + 1"));
+        let fanout = parse_statements("morphism split: A → (B, C) = (B: in, C: in)");
+        assert_eq!(fanout, parse_statements("morphism split: A -> (B, C) = (B: in, C: in)"));
+        assert_eq!(fanout.unwrap().1.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_generic_morphism() {
+        let (rest, stmt) = parse_statement("morphism f<W>: A<W> -> B<W> = in + 1").unwrap();
+        assert_eq!(rest, "");
+        assert!(matches!(stmt, Statement::Morphism { ref generic, from_width: None, to_width: None, .. } if generic.as_deref() == Some("W")));
+        assert_eq!(stmt.to_source(), "morphism f<W>: A<W> -> B<W> = in + 1");
+        // Both endpoints take the declared parameter, and nothing else
+        assert!(parse_statement("morphism f<W>: A -> B<W>").is_err());
+        assert!(parse_statement("morphism f<W>: A<W> -> B<V>").is_err());
+        assert_ne!(parse_statement("morphism f: A -> B<W>").unwrap().0, "");
+    }
+
+    #[test]
+    fn test_parse_identity() {
+        let expected = Statement::Identity { name: "id_A".to_string(), object: "A".to_string() };
+        assert_eq!(parse_statement("id A"), Ok(("", expected.clone())));
+        assert_eq!(parse_statement("morphism id_A := identity A"), Ok(("", expected.clone())));
+        assert_eq!(expected.to_source(), "id A");
+        let (_, renamed) = parse_statement("morphism pass := identity A").unwrap();
+        assert_eq!(renamed.to_source(), "morphism pass := identity A");
+        // Names starting with `id` are still objects and morphisms
+        assert!(matches!(parse_statement("morphism idle: A -> B"), Ok(("", Statement::Morphism { .. }))));
+    }
+
+    #[test]
+    fn test_parse_product() {
+        let expected = Statement::Product {
+            name: "C".to_string(),
+            components: vec!["A".to_string(), "B".to_string(), "D".to_string()],
+        };
+        assert_eq!(parse_statements("object C = A × B × D"), Ok(("", vec![expected.clone()])));
+        assert_eq!(parse_statement("object C = A * B * D"), Ok(("", expected.clone())));
+        assert_eq!(expected.to_source(), "object C = A × B × D");
+        // An initial value is still a number
+        assert!(matches!(parse_statements("object R : 8 = 8'h3C"), Ok(("", ref stmts)) if matches!(stmts[..], [Statement::Object { .. }])));
+    }
+
+    #[test]
+    fn test_parse_memory() {
+        let expected = Statement::Memory { name: "Mem".to_string(), width: WidthExpr::Ref("W".to_string()), depth: 256 };
+        assert_eq!(parse_statements("object Mem : ram W x 256"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "object Mem : ram W x 256");
+        assert!(parse_memory("object Mem : ram 8 x 0").is_err());
+        assert!(matches!(parse_statements("object ram : 8"), Ok(("", statements)) if matches!(statements[..], [Statement::Object { .. }])));
+    }
+
+    #[test]
+    fn test_parse_enum() {
+        let expected = Statement::Enum {
+            name: "State".to_string(),
+            members: vec!["IDLE".to_string(), "RUN".to_string(), "DONE".to_string()],
+        };
+        assert_eq!(parse_statements("object State = {IDLE,RUN, DONE}"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "object State = { IDLE, RUN, DONE }");
+
+        let (rest, table) = parse_table("morphism next: State -> State table { IDLE -> RUN, RUN -> 10, 1? -> IDLE }").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(table.to_source(), "morphism next: State -> State table { IDLE -> RUN, RUN -> 10, 1? -> IDLE }");
+    }
+
+    #[test]
+    fn test_parse_coproduct() {
+        let expected = Statement::Coproduct {
+            name: "C".to_string(),
+            components: vec!["A".to_string(), "B".to_string()],
+        };
+        assert_eq!(parse_statements("object C = A + B"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "object C = A + B");
+
+        let case = Statement::Case {
+            name: "h".to_string(),
+            from: "C".to_string(),
+            to: "D".to_string(),
+            branches: vec!["f".to_string(), "g".to_string()],
+        };
+        assert_eq!(parse_statement("morphism h: C → D = [f, g]"), Ok(("", case.clone())));
+        assert_eq!(case.to_source(), "morphism h: C -> D = [f, g]");
+    }
+
+    #[test]
+    fn test_parse_category() {
+        let (_, statements) = parse_statements("category Alu {\n    object A\n    morphism inc: A -> A = in + 1\n}").unwrap();
+        let names: Vec<String> = statements.iter().flat_map(Statement::declared_names).collect();
+        assert_eq!(names, ["Alu", "Alu_A", "Alu_inc"]);
+        assert_eq!(statements[0].to_source(), "category Alu { }");
+        assert_eq!(parse_statements("category Alu { }"), Ok(("", vec![Statement::Category { name: "Alu".to_string() }])));
+        // Scoped names spell out the prefix, except in chains where `.` may
+        // also compose, so they are kept for the declared names to settle
+        assert!(matches!(parse_statement("morphism f: X -> Alu.Sub.A"), Ok(("", Statement::Morphism { to, .. })) if to == "Alu_Sub_A"));
+        let (_, define) = parse_statement("define h = g.f ∘ Alu.add[3]").unwrap();
+        assert!(matches!(&define, Statement::Define { chain, .. } if *chain == ["g.f", "Alu.add_3"]));
+        assert_eq!(define.to_source(), "define h = g.f ∘ Alu.add_3");
+        assert!(matches!(parse_statement("define h = g .[4] f"), Ok(("", Statement::Define { chain, junction_widths, .. })) if chain == ["g", "f"] && junction_widths == [Some(4)]));
+        let (_, assertion) = parse_statement("assert commute: Alu.add == f[1] . f[0]").unwrap();
+        assert_eq!(assertion.to_source(), "assert commute: Alu.add == f_1 ∘ f_0");
+    }
+
+    #[test]
+    fn test_parse_tensor() {
+        let expected = Statement::Tensor {
+            name: "p".to_string(),
+            factors: vec!["f".to_string(), "g".to_string()],
+        };
+        assert_eq!(parse_statement("morphism p = f ⊗ g"), Ok(("", expected.clone())));
+        assert_eq!(expected.to_source(), "morphism p = f ⊗ g");
+        assert!(parse_statement("morphism p = f").map_or(true, |(rest, _)| !rest.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_trace() {
+        let expected = Statement::Trace { name: "fsm".to_string(), step: "step".to_string() };
+        assert_eq!(parse_statement("morphism fsm = trace( step )"), Ok(("", expected.clone())));
+        assert_eq!(expected.to_source(), "morphism fsm = trace(step)");
+    }
+
+    #[test]
+    fn test_parse_swap() {
+        let expected = Statement::Swap { name: "s".to_string(), first: "A".to_string(), second: "B".to_string() };
+        assert_eq!(parse_statements("morphism s = swap( A ,B )"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "morphism s = swap(A, B)");
+        assert!(parse_swap("morphism s = swap(A)").is_err());
+    }
+
+    #[test]
+    fn test_parse_dup() {
+        let expected = Statement::Dup { name: "d".to_string(), object: "A".to_string() };
+        assert_eq!(parse_statements("morphism d = dup( A )"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "morphism d = dup(A)");
+        assert!(parse_dup("morphism d = dup(A, B)").is_err());
+    }
+
+    #[test]
+    fn test_parse_projection() {
+        let expected = Statement::Projection { name: "p".to_string(), product: "C".to_string(), index: 2 };
+        assert_eq!(parse_statements("morphism p = pi2( C )"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "morphism p = pi2(C)");
+        assert!(parse_projection("morphism p = pi0(C)").is_err());
+        assert!(parse_projection("morphism p = pi(C)").is_err());
+    }
+
+    #[test]
+    fn test_parse_injection() {
+        let expected = Statement::Injection { name: "j".to_string(), coproduct: "C".to_string(), right: true };
+        assert_eq!(parse_statements("morphism j = inr( C )"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "morphism j = inr(C)");
+        assert_eq!(parse_injection("morphism i = inl(C)").map(|(_, stmt)| stmt.to_source()), Ok("morphism i = inl(C)".to_string()));
+        assert!(parse_injection("morphism i = inx(C)").is_err());
+    }
+
+    #[test]
+    fn test_parse_family() {
+        let expected = Statement::ObjectFamily {
+            name: "A".to_string(),
+            start: 0,
+            end: 7,
+            width: Some(WidthExpr::Const(8)),
+            signed: true,
+            fraction: None,
+        };
+        assert_eq!(parse_statements("object A[0..7] : signed 8"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "object A[0..7] : signed 8");
+        assert_eq!(expected.declared_names().len(), 8);
+        assert!(parse_family("object A[3..2]").is_err());
+        // Members are named by index anywhere a name is expected
+        assert!(matches!(parse_statement("morphism f: A[3] -> B"), Ok(("", Statement::Morphism { from, .. })) if from == "A_3"));
+        assert!(matches!(parse_statement("morphism lane[0..8]: A -> B"), Ok(("", Statement::Morphism { lanes: Some(8), .. }))));
+    }
+
+    #[test]
+    fn test_parse_for() {
+        let (rest, statements) = parse_statements("for i in 0..2 {\n    morphism f[i]: A[i] -> B[i] = in + 1\n}").unwrap();
+        assert_eq!(rest, "");
+        let names: Vec<String> = statements.iter().flat_map(Statement::declared_names).collect();
+        assert_eq!(names, ["f_0", "f_1", "f_2"]);
+        assert!(matches!(&statements[2], Statement::Morphism { from, to, .. } if from == "A_2" && to == "B_2"));
+        // Inner loops are unrolled first, and names outside the loop stay put
+        let (_, statements) = parse_statements("for i in 0..1 { for j in 1..2 { morphism g[i][j]: X -> Y } }").unwrap();
+        let names: Vec<String> = statements.iter().flat_map(Statement::declared_names).collect();
+        assert_eq!(names, ["g_0_1", "g_0_2", "g_1_1", "g_1_2"]);
+        assert!(matches!(&statements[0], Statement::Morphism { from, .. } if from == "X"));
+        assert!(parse_for("for i in 3..2 { object A[i] }").is_err());
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let expected = Statement::Table {
+            name: "dec".to_string(),
+            from: "A".to_string(),
+            to: "B".to_string(),
+            rows: vec![("00".to_string(), "01".to_string()), ("1?".to_string(), "10".to_string())],
+        };
+        assert_eq!(parse_statements("morphism dec: A -> B table { 00 -> 01, 1? -> 10 }"), Ok(("", vec![expected.clone()])));
+        assert_eq!(parse_statements("morphism dec: A → B table {\n    00 -> 01 // idle\n    1? -> 10\n}"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "morphism dec: A -> B table { 00 -> 01, 1? -> 10 }");
+        assert!(parse_table("morphism dec: A -> B table { 00 -> 0? }").map_or(true, |(rest, _)| !rest.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_rom() {
+        let expected = Statement::Rom {
+            name: "lut".to_string(),
+            from: "Addr".to_string(),
+            to: "Data".to_string(),
+            path: "tables/sine.hex".to_string(),
+        };
+        assert_eq!(parse_statements("morphism lut: Addr → Data rom \"tables/sine.hex\" // 256 words"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "morphism lut: Addr -> Data rom \"tables/sine.hex\"");
+        assert!(parse_rom("morphism lut: Addr -> Data rom sine.hex").is_err());
+    }
+
+    #[test]
+    fn test_parse_template() {
+        let template = Statement::Template {
+            name: "pipeline".to_string(),
+            params: vec!["X".to_string(), "Y".to_string(), "n".to_string()],
+            body: "object M : n\n  seq morphism first: X -> M = in".to_string(),
+        };
+        let (rest, statements) = parse_statements("template pipeline(X, Y, n) {\n  object M : n\n  seq morphism first: X -> M = in\n}").unwrap();
+        assert_eq!((rest, statements), ("", vec![template.clone()]));
+        assert_eq!(template.to_source(), "template pipeline(X, Y, n) { object M : n\n  seq morphism first: X -> M = in }");
+        assert!(parse_template("template empty() {  }").is_err());
+
+        let expected = Statement::Use {
+            template: "lib_pipeline".to_string(),
+            args: vec!["A".to_string(), "B_3".to_string(), "8".to_string()],
+            alias: Some("p1".to_string()),
+        };
+        assert_eq!(parse_statements("use lib.pipeline(A, B[3], 8) as p1"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "use lib_pipeline(A, B_3, 8) as p1");
+        let (_, statements) = parse_statements("section s { use pipeline(A, 8) }").unwrap();
+        assert_eq!(statements[0].to_source(), "use pipeline(A, 8) as s");
+    }
+
+    #[test]
+    fn test_parse_verilog() {
+        let expected = Statement::Verilog {
+            name: "f".to_string(),
+            from: "A".to_string(),
+            to: "B".to_string(),
+            text: "assign out = {in[0], in[7:1]};".to_string(),
+        };
+        assert_eq!(parse_statements("morphism f: A -> B verilog {  assign out = {in[0], in[7:1]};  }"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "morphism f: A -> B verilog { assign out = {in[0], in[7:1]}; }");
+        assert!(parse_verilog("morphism f: A -> B verilog { assign out = {in; }").is_err());
+        assert!(parse_verilog("morphism f: A -> B verilog { }").is_err());
+    }
+
+    #[test]
+    fn test_parse_library() {
+        let expected = Statement::Library {
+            name: "s".to_string(),
+            element: "add".to_string(),
+            from: vec!["A".to_string(), "B".to_string()],
+            to: "C".to_string(),
+        };
+        assert_eq!(parse_statements("morphism s = std.add( A, B ) → C"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "morphism s = std.add(A, B) -> C");
+    }
+
+    #[test]
+    fn test_parse_reduce() {
+        let (rest, statements) = parse_statements("morphism p = reduce( std.xor, A[0..2] ) -> P").unwrap();
+        assert_eq!(rest, "");
+        let source: Vec<String> = statements.iter().map(Statement::to_source).collect();
+        assert_eq!(source, [
+            "wire p_0_0_out : max(A_0, A_1)",
+            "morphism p_0_0 = std.xor(A_0, A_1) -> p_0_0_out",
+            "morphism p = std.xor(p_0_0_out, A_2) -> P",
+        ]);
+        for stmt in &statements {
+            assert_eq!(parse_statements(&stmt.to_source()), Ok(("", vec![stmt.clone()])));
+        }
+        let (_, statements) = parse_statements("morphism sum = reduce(std.add, A[0..7]) -> S").unwrap();
+        let names: Vec<String> = statements.iter().flat_map(Statement::declared_names).collect();
+        assert_eq!(names, [
+            "sum_0_0_out", "sum_0_0", "sum_0_1_out", "sum_0_1", "sum_0_2_out", "sum_0_2", "sum_0_3_out", "sum_0_3",
+            "sum_1_0_out", "sum_1_0", "sum_1_1_out", "sum_1_1", "sum",
+        ]);
+        assert_eq!(statements[8].to_source(), "wire sum_1_0_out : max(sum_0_0_out, sum_0_1_out) + 1");
+        assert!(parse_reduce("morphism c = reduce(std.cmp, A[0..3]) -> C").is_err());
+        assert!(parse_reduce("morphism c = reduce(std.add, A[3..3]) -> C").is_err());
+    }
+
+    #[test]
+    fn test_parse_top() {
+        let expected = Statement::Top { name: "MyDesign".to_string() };
+        assert_eq!(parse_statements("top MyDesign // integrated twice"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "top MyDesign");
+        assert!(parse_top("top \"my design\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_pragma() {
+        let expected = Statement::Pragma { target: "f".to_string(), text: "(* keep_hierarchy = \"yes\" *)".to_string() };
+        assert_eq!(parse_statements("pragma f \"(* keep_hierarchy = \\\"yes\\\" *)\""), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "pragma f \"(* keep_hierarchy = \\\"yes\\\" *)\"");
+        assert_eq!(parse_pragma("pragma A \"\""), Ok(("", Statement::Pragma { target: "A".to_string(), text: String::new() })));
+        assert!(parse_pragma("pragma A \"(* keep *)").is_err());
+    }
+
+    #[test]
+    fn test_parse_functor() {
+        let expected = Statement::Functor {
+            name: "F".to_string(),
+            from: "alu".to_string(),
+            to: "core".to_string(),
+            mapping: vec![("A".to_string(), "X".to_string()), ("inc".to_string(), "step".to_string())],
+        };
+        assert_eq!(parse_statement("functor F: alu -> core { A => X, inc => step }"), Ok(("", expected.clone())));
+        assert_eq!(parse_statement("functor F: alu → core {\n    A => X // input\n    inc => step\n}"), Ok(("", expected.clone())));
+        assert_eq!(expected.to_source(), "functor F: alu -> core { A => X, inc => step }");
+        assert!(matches!(parse_statement("functor G: alu -> core {}"), Ok(("", Statement::Functor { mapping, .. })) if mapping.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_nat() {
+        let bare = Statement::Nat { name: "eta".to_string(), from: "F".to_string(), to: "G".to_string(), components: Vec::new() };
+        assert_eq!(parse_statement("nat eta: F => G"), Ok(("", bare.clone())));
+        assert_eq!(bare.to_source(), "nat eta: F => G");
+
+        let (rest, stmt) = parse_statement("nat eta: F => G {\n    X = in + 1\n    Y = in\n}").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(stmt.to_source(), "nat eta: F => G { X = in + 1, Y = in }");
+        assert_eq!(parse_statement(&stmt.to_source()), Ok(("", stmt)));
+    }
+
+    #[test]
+    fn test_parse_constant() {
+        let zero = Statement::Constant { name: "zero".to_string(), to: "A".to_string(), value: "8'h00".to_string() };
+        assert_eq!(parse_statement("morphism zero: I -> A = 8'h00"), Ok(("", zero.clone())));
+        assert_eq!(parse_statement("morphism zero: I → A = 8'h00 // reset value"), Ok(("", zero.clone())));
+        assert_eq!(parse_statement(&zero.to_source()), Ok(("", zero)));
+        // A body that is more than a literal reads a declared object I
+        assert!(matches!(parse_statement("morphism f: I -> A = 1 + in"), Ok(("", Statement::Morphism { .. }))));
+        assert!(matches!(parse_statement("morphism f: J -> A = 1"), Ok(("", Statement::Morphism { .. }))));
+    }
+
+    #[test]
+    fn test_parse_multi_input() {
+        let (rest, stmt) = parse_statement("morphism add: (A, B) -> C = A + B").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(stmt, Statement::MultiInput {
+            name: "add".to_string(),
+            from: vec!["A".to_string(), "B".to_string()],
+            to: "C".to_string(),
+            body: Some(Expr::Binary {
+                op: BinOp::Add,
+                lhs: Box::new(Expr::Ident("A".to_string())),
+                rhs: Box::new(Expr::Ident("B".to_string())),
+            }),
+            registered: false,
+        });
+        assert_eq!(parse_statement(&stmt.to_source()), Ok(("", stmt)));
+        let (_, registered) = parse_statement("seq morphism acc: (A, B) -> C = A + B").unwrap();
+        assert!(matches!(registered, Statement::MultiInput { registered: true, .. }));
+        assert_eq!(registered.to_source(), "seq morphism acc: (A, B) -> C = A + B");
+        let (_, block) = parse_statement("morphism mac: (A, B, C) → D {\n    out = A * B + C\n}").unwrap();
+        assert_eq!(block.to_source(), "morphism mac: (A, B, C) -> D = A * B + C");
+        // Section prefixes reach the objects the body reads
+        let (_, statements) = parse_statements("section s { object A\n object B\n object C\n morphism add: (A, B) -> C = A + B[3:0] }").unwrap();
+        assert_eq!(statements[3].to_source(), "morphism s_add: (s_A, s_B) -> s_C = s_A + s_B[3:0]");
+        assert!(parse_multi_input("morphism f: (A) -> B").is_err());
+    }
+
+    #[test]
+    fn test_parse_import() {
+        let plain = Statement::Import { path: "lib/adders.cat".to_string(), alias: None };
+        assert_eq!(parse_statement("import \"lib/adders.cat\""), Ok(("", plain.clone())));
+        assert_eq!(plain.to_source(), "import \"lib/adders.cat\"");
+        let (rest, stmt) = parse_statement("import \"lib/adders.cat\" as adders").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(parse_statement(&stmt.to_source()), Ok(("", stmt)));
+        // Inside a section the file is imported under the section's prefix
+        let (_, statements) = parse_statements("section core { import \"a.cat\" as lib }").unwrap();
+        assert_eq!(statements, vec![Statement::Import { path: "a.cat".to_string(), alias: Some("core_lib".to_string()) }]);
+        assert!(parse_statement("import lib/adders.cat").is_err());
+    }
+
+    #[test]
+    fn test_parse_body_block() {
+        let inline = parse_statement("morphism f: A -> B = in * 3 + 1");
+        assert_eq!(parse_statement("morphism f: A -> B { out = in * 3 + 1 }"), inline);
+        assert_eq!(parse_statement("morphism f: A -> B {\n    // triple, then step\n    out = in * 3 + 1\n}"), inline);
+        let (rest, stmt) = parse_statement("seq morphism f: A -> B { out = in } @handshake").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(stmt.to_source(), "seq morphism f: A -> B = in @handshake");
+        assert!(parse_statement("morphism f: A -> B { in + 1 }").map_or(true, |(rest, _)| !rest.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_bindings() {
+        let (rest, stmt) = parse_statement("morphism f: A -> B {\n    let t = in >> 2\n    let u = t + 1; out = t + u;\n}").unwrap();
+        assert_eq!(rest, "");
+        let Statement::Morphism { ref bindings, .. } = stmt else {
+            panic!("expected a morphism");
+        };
+        let names: Vec<&str> = bindings.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["t", "u"]);
+        assert_eq!(stmt.to_source(), "morphism f: A -> B { let t = in >> 2; let u = t + 1; out = t + u }");
+        assert_eq!(parse_statement(&stmt.to_source()), Ok(("", stmt)));
+        // A multi-input body reads its inputs, not bindings
+        assert!(parse_statement("morphism m: (A, B) -> C { let t = A; out = t }").map_or(true, |(rest, _)| !rest.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_attributes() {
+        let (rest, stmt) = parse_statement("@pipeline(2) @keep seq morphism f: A -> B = in + 1").unwrap();
+        assert_eq!(rest, "");
+        let Statement::Morphism { ref attributes, registered: true, .. } = stmt else {
+            panic!("expected a registered morphism, got {:?}", stmt);
+        };
+        assert_eq!(attributes, &[
+            Attribute { name: "pipeline".to_string(), args: vec!["2".to_string()] },
+            Attribute { name: "keep".to_string(), args: Vec::new() },
+        ]);
+        assert_eq!(stmt.to_source(), "@pipeline(2) @keep seq morphism f: A -> B = in + 1");
+        assert_eq!(parse_statement(&stmt.to_source()), Ok(("", stmt)));
+
+        let (_, fanout) = parse_statements("@latency(1, cycles) morphism split: A -> (B, C) = (B: in[3:0], C: in[7:4])").unwrap();
+        assert!(fanout.iter().all(|stmt| matches!(stmt, Statement::Morphism { attributes, .. } if attributes[0].args == ["1", "cycles"])));
+        assert!(parse_statement("@pipeline() morphism f: A -> B").is_err());
+    }
+
+    #[test]
+    fn test_parse_maybe() {
+        let (rest, stmt) = parse_statement("morphism f: maybe A : 8 -> maybe B = in + 1").unwrap();
+        assert_eq!(rest, "");
+        assert!(matches!(&stmt, Statement::Morphism { maybe: true, handshake: false, from, to, .. } if from == "A" && to == "B"));
+        assert_eq!(stmt.to_source(), "morphism f: maybe A : 8 -> maybe B = in + 1");
+        // Without an object after it, `maybe` is the object's own name
+        assert!(matches!(parse_statement("morphism f: maybe -> B"), Ok(("", Statement::Morphism { maybe: false, .. }))));
+        assert!(parse_statement("morphism f: maybe A -> B").is_err());
+    }
+
+    #[test]
+    fn test_parse_handshake() {
+        let (rest, stmt) = parse_statement("seq morphism f: A -> B = in + 1 @handshake @ifdef DEBUG").unwrap();
+        assert_eq!(rest, "");
+        assert!(matches!(stmt, Statement::Morphism { handshake: true, registered: true, .. }));
+        assert_eq!(stmt.to_source(), "seq morphism f: A -> B = in + 1 @handshake @ifdef DEBUG");
+        assert!(matches!(parse_statement("morphism f: A -> B").unwrap().1, Statement::Morphism { handshake: false, .. }));
+    }
+
+    #[test]
+    fn test_parse_define() {
+        let (rest, stmt) = parse_statement("define h = k . g ∘[4] f").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(stmt, Statement::Define {
+            name: "h".to_string(),
+            chain: vec!["k".to_string(), "g".to_string(), "f".to_string()],
+            junction_widths: vec![None, Some(4)],
+        });
+        assert_eq!(stmt.to_source(), "define h = k ∘ g ∘[4] f");
+        assert_eq!(parse_statement("let h = k . g ∘[4] f"), Ok(("", stmt)));
+        assert!(parse_statement("define h = g").is_err());
+        assert!(parse_statement("define h = g ∘[0] f").map_or(true, |(rest, _)| !rest.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_test_vector() {
+        assert_eq!(
+            parse_statement("test f: in 8'd5 => out 8'd6"),
+            Ok(("", Statement::Test {
+                morphism: "f".to_string(),
+                input: "8'd5".to_string(),
+                expected: "8'd6".to_string(),
+            }))
+        );
+        assert_eq!(parse_statement("test f: in 5  =>  out 'hx").unwrap().1.to_source(), "test f: in 5 => out 'hx");
+        assert!(parse_statement("test f: in => out 6").is_err());
+    }
+
+    #[test]
+    fn test_parse_object_list() {
+        assert_eq!(
+            parse_statements("object A, B"),
+            Ok(("", vec![
+                Statement::Object { name: "A".to_string(), width: None, signed: false, initial: None, active_low: false, fraction: None, domain: None },
+                Statement::Object { name: "B".to_string(), width: None, signed: false, initial: None, active_low: false, fraction: None, domain: None },
+            ]))
+        );
+        let (rest, objects) = parse_statements("object A,\"b c\" , C : signed 4").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(objects.len(), 3);
+        assert!(objects.iter().all(|stmt| matches!(
+            stmt,
+            Statement::Object { width: Some(WidthExpr::Const(4)), signed: true, .. }
+        )));
+        assert_eq!(parse_statements("morphism f: A -> B").unwrap().1.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_quoted_identifier() {
+        assert_eq!(
+            parse_object("object \"weird name\""),
+            Ok(("", Statement::Object { name: "weird name".to_string(), width: None, signed: false, initial: None, active_low: false, fraction: None, domain: None }))
+        );
+        assert_eq!(
+            parse_morphism("morphism \"f-1\": \"weird name\" -> B"),
+            Ok(("", Statement::Morphism {
+                name: "f-1".to_string(),
+                from: "weird name".to_string(),
+                to: "B".to_string(),
+                from_width: None,
+                to_width: None,
+                body: None,
+                bindings: Vec::new(),
+                lanes: None,
+                registered: false,
+                guard: None,
+                external: None,
+                handshake: false,
+                maybe: false,
+                generic: None,
+                attributes: Vec::new(),
+            }))
+        );
+        assert!(parse_object("object \"\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_object_width_expr() {
+        assert_eq!(
+            parse_object("object Sum : A + 1"),
+            Ok(("", Statement::Object {
+                name: "Sum".to_string(),
+                width: Some(WidthExpr::Add(
+                    Box::new(WidthExpr::Ref("A".to_string())),
+                    Box::new(WidthExpr::Const(1)),
+                )),
+                signed: false,
+                initial: None,
+                active_low: false,
+                fraction: None,
+                domain: None,
+            }))
+        );
+        assert_eq!(
+            width_expr("2 + A * (B + 1)"),
+            Ok(("", WidthExpr::Add(
+                Box::new(WidthExpr::Const(2)),
+                Box::new(WidthExpr::Mul(
+                    Box::new(WidthExpr::Ref("A".to_string())),
+                    Box::new(WidthExpr::Add(
+                        Box::new(WidthExpr::Ref("B".to_string())),
+                        Box::new(WidthExpr::Const(1)),
+                    )),
+                )),
+            )))
+        );
+        assert_eq!(
+            width_expr("max(A, 4) + 1"),
+            Ok(("", WidthExpr::Add(
+                Box::new(WidthExpr::Max(
+                    Box::new(WidthExpr::Ref("A".to_string())),
+                    Box::new(WidthExpr::Const(4)),
+                )),
+                Box::new(WidthExpr::Const(1)),
+            )))
+        );
+        // Without a parenthesis `max` is just an object
+        assert_eq!(width_expr("max"), Ok(("", WidthExpr::Ref("max".to_string()))));
+    }
+
+    #[test]
+    fn test_parse_morphism() {
+        assert_eq!(
+            parse_morphism("morphism f: A -> B"),
+            Ok(("", Statement::Morphism {
+                name: "f".to_string(),
+                from: "A".to_string(),
+                to: "B".to_string(),
+                from_width: None,
+                to_width: None,
+                body: None,
+                bindings: Vec::new(),
+                lanes: None,
+                registered: false,
+                guard: None,
+                external: None,
+                handshake: false,
+                maybe: false,
+                generic: None,
+                attributes: Vec::new(),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_morphism_lanes_and_body() {
+        assert_eq!(
+            parse_morphism("morphism lane[0..8]: A -> B = in * 3 + 1"),
+            Ok(("", Statement::Morphism {
+                name: "lane".to_string(),
+                from: "A".to_string(),
+                to: "B".to_string(),
+                from_width: None,
+                to_width: None,
+                body: Some(Expr::Binary {
+                    op: BinOp::Add,
+                    lhs: Box::new(Expr::Binary {
+                        op: BinOp::Mul,
+                        lhs: Box::new(Expr::Ident("in".to_string())),
+                        rhs: Box::new(Expr::Literal(3)),
+                    }),
+                    rhs: Box::new(Expr::Literal(1)),
+                }),
+                bindings: Vec::new(),
+                lanes: Some(8),
+                registered: false,
+                guard: None,
+                external: None,
+                handshake: false,
+                maybe: false,
+                generic: None,
+                attributes: Vec::new(),
+            }))
+        );
+        assert_eq!(
+            parse_morphism("seq morphism r: A -> B"),
+            Ok(("", Statement::Morphism {
+                name: "r".to_string(),
+                from: "A".to_string(),
+                to: "B".to_string(),
+                from_width: None,
+                to_width: None,
+                body: None,
+                bindings: Vec::new(),
+                lanes: None,
+                registered: true,
+                guard: None,
+                external: None,
+                handshake: false,
+                maybe: false,
+                generic: None,
+                attributes: Vec::new(),
+            }))
+        );
+        assert_eq!(
+            parse_morphism("morphism dbg: A -> B = in @ifdef DEBUG"),
+            Ok(("", Statement::Morphism {
+                name: "dbg".to_string(),
+                from: "A".to_string(),
+                to: "B".to_string(),
+                from_width: None,
+                to_width: None,
+                body: Some(Expr::Ident("in".to_string())),
+                bindings: Vec::new(),
+                lanes: None,
+                registered: false,
+                guard: Some("DEBUG".to_string()),
+                external: None,
+                handshake: false,
+                maybe: false,
+                generic: None,
+                attributes: Vec::new(),
+            }))
+        );
+        assert!(parse_morphism("morphism lane[4..4]: A -> B").is_err());
+    }
+
+    #[test]
+    fn test_parse_expr_precedence() {
+        let (rest, parsed) = expr("in << 2 + 1 | in & 3").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(parsed.to_source(), "in << 2 + 1 | in & 3");
+        assert_eq!(
+            parsed,
+            Expr::Binary {
+                op: BinOp::Or,
+                lhs: Box::new(Expr::Binary {
+                    op: BinOp::Shl,
+                    lhs: Box::new(Expr::Ident("in".to_string())),
+                    rhs: Box::new(Expr::Binary {
+                        op: BinOp::Add,
+                        lhs: Box::new(Expr::Literal(2)),
+                        rhs: Box::new(Expr::Literal(1)),
+                    }),
+                }),
+                rhs: Box::new(Expr::Binary {
+                    op: BinOp::And,
+                    lhs: Box::new(Expr::Ident("in".to_string())),
+                    rhs: Box::new(Expr::Literal(3)),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_bitwise() {
+        // `^` binds between `|` and `&`, and `~` tighter than any binary operator
+        let (rest, parsed) = expr("in | in ^ ~in[3:0] & 5 ^ 1").unwrap();
+        assert_eq!(rest, "");
+        let Expr::Binary { op: BinOp::Or, rhs, .. } = &parsed else {
+            panic!("expected `|` at the root");
+        };
+        let Expr::Binary { op: BinOp::Xor, lhs, .. } = rhs.as_ref() else {
+            panic!("expected `^` under `|`");
+        };
+        assert!(matches!(lhs.as_ref(), Expr::Binary { op: BinOp::Xor, rhs, .. } if matches!(rhs.as_ref(), Expr::Binary { op: BinOp::And, .. })));
+        assert_eq!(parsed.to_verilog("in_A"), "in_A | in_A ^ ~in_A[3:0] & 5 ^ 1");
+        assert_eq!(expr("~ (in + 1) ^ ~in").unwrap().1.to_source(), "~(in + 1) ^ ~in");
+        assert_eq!(expr("(in ^ 3) & 1").unwrap().1.to_source(), "(in ^ 3) & 1");
+        assert_eq!(expr("~in").unwrap().1.infer_width(&|_| Some(6)), Some(6));
+    }
+
+    #[test]
+    fn test_parse_conditional() {
+        let (rest, parsed) = expr("if in >= 10 then in - 10 else if in == 3 then 0 else in + 1").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(parsed.to_source(), "if in >= 10 then in - 10 else if in == 3 then 0 else in + 1");
+        assert_eq!(parsed.to_verilog("in_A"), "in_A >= 10 ? in_A - 10 : in_A == 3 ? 0 : in_A + 1");
+        let Expr::If { condition, .. } = &parsed else {
+            panic!("expected a conditional");
+        };
+        assert!(matches!(condition.as_ref(), Expr::Binary { op: BinOp::Ge, .. }));
+        // Comparisons bind looser than shifts, and a conditional operand needs parentheses
+        assert_eq!(expr("in << 1 < 4 != 0").unwrap().1.to_source(), "in << 1 < 4 != 0");
+        let (_, sum) = expr("(if in > 1 then 1 else 2) + 1").unwrap();
+        assert_eq!(sum.to_verilog("in"), "(in > 1 ? 1 : 2) + 1");
+        let (_, nested) = expr("if in > 1 then (if in > 2 then 1 else 2) else 3").unwrap();
+        assert_eq!(expr(&nested.to_source()), Ok(("", nested)));
+        assert_eq!(expr("iffy").unwrap().1, Expr::Ident("iffy".to_string()));
+    }
+
+    #[test]
+    fn test_parse_concatenation() {
+        let (rest, parsed) = expr("{in[3:0], { in[5:4] , in[7:6] }} + 1").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(parsed.to_source(), "{in[3:0], {in[5:4], in[7:6]}} + 1");
+        assert_eq!(parsed.to_verilog("in_A"), "{in_A[3:0], {in_A[5:4], in_A[7:6]}} + 1");
+        assert_eq!(parsed.infer_width(&|_| Some(8)), Some(9));
+        assert!(expr("{}").is_err());
+    }
+
+    #[test]
+    fn test_parse_category_file() {
+        let source = "object A\nobject B : A + 1\n\n  morphism f: A -> B\nassert commute: f == f\n";
+        let (rest, ast) = parse_category_file(source).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(ast.statements.len(), 4);
+        assert_eq!(ast.to_source(), "object A\nobject B : A + 1\nmorphism f: A -> B\nassert commute: f == f\n");
+    }
+
+    #[test]
+    fn test_parse_semicolons() {
+        let source = "object A; object B : 4;\nmorphism f: A -> B = in + 1 ; // note\nsection s { object C; object D }\nfor i in 0..1 { object E[i]; }";
+        let (rest, ast) = parse_category_file(source).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(ast.to_source(), "object A\nobject B : 4\nmorphism f: A -> B = in + 1\nobject s_C\nobject s_D\nobject E_0\nobject E_1\n");
+        // A `;` keeps Verilog bodies and strings to themselves
+        let (rest, ast) = parse_category_file("morphism p: A -> B verilog { assign out = ~in; }; pragma p \"(* a; b *)\"").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(ast.statements.len(), 2);
+        assert_eq!(parse_category_file("object A;;object B").unwrap().0, ";object B");
+        // A constant ends at the `;` like any other statement
+        let (rest, ast) = parse_category_file("object A : 8; morphism zero: I -> A = 8'h00; object B").unwrap();
+        assert_eq!(rest, "");
+        assert!(matches!(&ast.statements[1], Statement::Constant { name, value, .. } if name == "zero" && value == "8'h00"));
+        assert!(matches!(&ast.statements[2], Statement::Object { name, .. } if name == "B"));
+    }
+
+    #[test]
+    fn test_parse_line_comments() {
+        let source = "// header\nobject A // input\n\n// f doubles A\nmorphism f: A -> A = A + A\nsection s { // nested\n  object B\n  // object C\n}\n// trailer";
+        let (rest, ast) = parse_category_file(source).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(ast.to_source(), "object A\nmorphism f: A -> A = A + A\nobject s_B\n");
+    }
+
+    #[test]
+    fn test_parse_block_comments() {
+        let source = "/* header\n   spanning lines */\nobject A /* input */\n/* morphism g: A -> A\n*/ object B /* a */ /* b */ // c\n/* trailer */";
+        let (rest, ast) = parse_category_file(source).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(ast.to_source(), "object A\nobject B\n");
+        assert_eq!(parse_category_file("object A /* unclosed").unwrap().0, "/* unclosed");
+    }
+
+    #[test]
+    fn test_parse_interface() {
+        assert_eq!(
+            parse_interface("interface Bus { data: 32, valid: 1 }"),
+            Ok(("", Statement::Interface {
+                name: "Bus".to_string(),
+                fields: vec![("data".to_string(), 32), ("valid".to_string(), 1)],
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_assert_commute() {
+        assert_eq!(
+            parse_assert_commute("assert commute: g ∘ f == h"),
+            Ok(("", Statement::AssertCommute {
+                lhs: Composition::from_chain(vec!["g".to_string(), "f".to_string()]),
+                rhs: Composition::Morphism("h".to_string()),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_assert_equal() {
+        let expected = Statement::AssertEqual { lhs: "f".to_string(), rhs: "g".to_string() };
+        assert_eq!(parse_statements("assert equal: f==g // same logic"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "assert equal: f == g");
+        assert!(parse_assert_equal("assert equal: g ∘ f == h").is_err());
+    }
+
+    #[test]
+    fn test_parse_assert_kind() {
+        let expected = Statement::AssertKind { morphism: "f".to_string(), kind: MorphismKind::Epi };
+        assert_eq!(parse_statements("assert epi f"), Ok(("", vec![expected.clone()])));
+        assert_eq!(expected.to_source(), "assert epi f");
+        assert_eq!(parse_assert_kind("assert  mono g").map(|(_, stmt)| stmt.to_source()), Ok("assert mono g".to_string()));
+        assert!(parse_assert_kind("assert monof").is_err());
+    }
+
+    #[test]
+    fn test_parse_parenthesised_composition() {
+        let (rest, stmt) = parse_assert_commute("assert commute: (h ∘ g) ∘ f == h ∘ ( g . f )").unwrap();
+        assert_eq!(rest, "");
+        let Statement::AssertCommute { lhs, rhs } = &stmt else { panic!("{:?}", stmt) };
+        let morphism = |name: &str| Box::new(Composition::Morphism(name.to_string()));
+        assert_eq!(*lhs, Composition::Compose(Box::new(Composition::Compose(morphism("h"), morphism("g"))), morphism("f")));
+        assert_eq!(*rhs, Composition::from_chain(vec!["h".to_string(), "g".to_string(), "f".to_string()]));
+        assert_eq!(lhs.chain(), rhs.chain());
+        assert_eq!(stmt.to_source(), "assert commute: (h ∘ g) ∘ f == h ∘ g ∘ f");
+        assert_eq!(parse_assert_commute("assert commute: (f ; g) ∘ k == h").unwrap().1, parse_assert_commute("assert commute: (g ∘ f) ∘ k == h").unwrap().1);
+        assert_eq!(parse_assert_commute("assert commute: ((f)) == f").unwrap().1.to_source(), "assert commute: f == f");
+        assert!(parse_assert_commute("assert commute: (g ∘ f == h").is_err());
+    }
+
+    #[test]
+    fn test_parse_composition_aliases() {
+        let expected = parse_assert_commute("assert commute: k ∘ g ∘ f == h");
+        assert_eq!(parse_assert_commute("assert commute: k . g . f == h"), expected);
+        assert_eq!(parse_assert_commute("assert commute: f ; g ; k == h"), expected);
+        assert_eq!(parse_assert_commute("assert commute: f;g;k == h"), expected);
+        assert!(parse_assert_commute("assert commute: k . g ; f == h").is_err());
+    }
+
+    #[test]
+    fn test_parse_spaced_o_composition() {
+        let expected = parse_assert_commute("assert commute: k ∘ g ∘ f == h");
+        assert_eq!(parse_assert_commute("assert commute: k o g o f == h"), expected);
+        assert_eq!(parse_assert_commute("assert commute: k o g . f == h"), expected);
+        let define = |source: &str| match parse_statement(source) {
+            Ok(("", Statement::Define { chain, junction_widths, .. })) => Some((chain, junction_widths)),
+            _ => None,
+        };
+        let chain = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        assert_eq!(define("let h = k o g o[4] f"), Some((chain(&["k", "g", "f"]), vec![None, Some(4)])));
+        // `o` is still a name wherever it isn't an operator
+        assert_eq!(define("define h = o ∘ f"), Some((chain(&["o", "f"]), vec![None])));
+        assert_eq!(define("define h = g o o"), Some((chain(&["g", "o"]), vec![None])));
+        // Without spaces on both sides `o` is part of a name
+        assert!(matches!(parse_assert_commute("assert commute: kog == h"), Ok(("", Statement::AssertCommute { ref lhs, .. })) if *lhs == Composition::Morphism("kog".to_string())));
+        assert_eq!(define("define h = g of"), None);
+        assert_eq!(define("define h = g o"), None);
+        assert_eq!(define("define h = g o[0] f"), None);
+        // It composes right to left, so it can't be mixed with `;`
+        assert!(parse_assert_commute("assert commute: k o g ; f == h").is_err());
     }
 } 
\ No newline at end of file
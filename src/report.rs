@@ -0,0 +1,242 @@
+//! Human-readable design summary generated alongside the RTL
+
+use crate::ast::{composition_source, CategoryAST, Statement, UNIT_OBJECT};
+use crate::dag::{CategoryDAG, Netlist, ObjectDirection};
+use crate::parser::parse_category_file;
+
+/// Compile a description to Verilog and a markdown design report
+pub fn compile_with_report(source: &str) -> Result<(String, String), String> {
+    let (rest, ast) = parse_category_file(source).map_err(|e| format!("Parse error: {}", e))?;
+    if !rest.is_empty() {
+        return Err(format!("Parse error: unexpected input `{}`", rest.lines().next().unwrap_or(rest)));
+    }
+    let dag = CategoryDAG::from_ast(&ast)?;
+    let netlist = Netlist::from_dag(&dag, &ast)?;
+    Ok((netlist.to_verilog(), markdown_report(&ast, &dag)))
+}
+
+/// Summarize objects, morphisms, assertions and DAG statistics as markdown
+pub fn markdown_report(ast: &CategoryAST, dag: &CategoryDAG) -> String {
+    // The DAG was built from the description, so it expands
+    let expanded = ast.expanded();
+    let ast = expanded.as_deref().unwrap_or(ast);
+    let mut report = String::from("# Design report\n\n## Objects\n\n");
+    report.push_str("| Object | Width | Direction |\n|---|---|---|\n");
+    // The unit object carries no bits and never becomes a signal
+    for obj in ast.get_objects().into_iter().chain(ast.get_wires()).filter(|obj| *obj != UNIT_OBJECT) {
+        let width = match (dag.object_width(obj), dag.object_fraction(obj)) {
+            (Some(w), 0) => w.to_string(),
+            (Some(w), f) => format!("fixed<{},{}>", w - f, f),
+            (None, _) => "?".to_string(),
+        };
+        let signed = if dag.object_signed(obj) { " signed" } else { "" };
+        let domain = dag.object_domain(obj).map_or(String::new(), |clock| format!(" @ {}", code(clock)));
+        let direction = match dag.object_direction(obj) {
+            _ if dag.is_wire(obj) => "wire",
+            Some(ObjectDirection::Input) => "input",
+            Some(ObjectDirection::Output) => "output",
+            Some(ObjectDirection::Internal) => "internal",
+            None => "?",
+        };
+        report.push_str(&format!("| {} | {}{}{} | {} |\n", code(obj), width, signed, domain, direction));
+    }
+
+    report.push_str("\n## Morphisms\n\n| Morphism | Source | Target | Kind | Body |\n|---|---|---|---|---|\n");
+    let morphisms = ast.get_morphisms();
+    let coproducts = ast.get_coproducts();
+    for stmt in &ast.statements {
+        match stmt {
+            Statement::Morphism { name, from, to, body, lanes, registered, external, handshake, maybe, generic, attributes, .. } => {
+                let mut kind = match (external, registered) {
+                    (Some(_), _) => "black box",
+                    (None, true) => "registered",
+                    (None, false) => "combinational",
+                }
+                .to_string();
+                if let Some(lanes) = lanes {
+                    kind.push_str(&format!(", {} lanes", lanes));
+                }
+                if *maybe {
+                    kind.push_str(", maybe");
+                } else if *handshake {
+                    kind.push_str(", handshake");
+                }
+                if let Some(generic) = generic {
+                    kind.push_str(&format!(", generic over {}", generic));
+                }
+                for attribute in attributes {
+                    kind.push_str(&format!(", {}", code(&attribute.to_source())));
+                }
+                let body = match (body, external) {
+                    (Some(body), _) => code(&body.to_source()),
+                    (None, Some(path)) => code(path),
+                    (None, None) if coproducts.iter().any(|(name, components)| *name == to && components.contains(from)) => "injection".to_string(),
+                    (None, None) if dag.sink_source(name).is_some() => "discarded".to_string(),
+                    (None, None) => "placeholder".to_string(),
+                };
+                report.push_str(&format!("| {} | {} | {} | {} | {} |\n", code(name), code(from), code(to), kind, body));
+            }
+            Statement::Define { name, chain, junction_widths } => {
+                // Endpoints are only known once the chain is typed
+                let (from, to) = morphisms.get(name).map_or(("?".to_string(), "?".to_string()), |(from, to)| (code(from), code(to)));
+                let body = code(&composition_source(chain, junction_widths));
+                report.push_str(&format!("| {} | {} | {} | composite | {} |\n", code(name), from, to, body));
+            }
+            Statement::Iso { name, inverse, from, to, body, inverse_body } => {
+                for (name, from, to, kind, body) in [(name, from, to, "iso", body), (inverse, to, from, "iso inverse", inverse_body)] {
+                    let body = body.as_ref().map_or("placeholder".to_string(), |body| code(&body.to_source()));
+                    report.push_str(&format!("| {} | {} | {} | {} | {} |\n", code(name), code(from), code(to), kind, body));
+                }
+            }
+            Statement::Identity { name, object } => {
+                report.push_str(&format!("| {} | {} | {} | identity | `in` |\n", code(name), code(object), code(object)));
+            }
+            Statement::MultiInput { name, from, to, body, registered } => {
+                let sources: Vec<String> = from.iter().map(|object| code(object)).collect();
+                let body = body.as_ref().map_or("placeholder".to_string(), |body| code(&body.to_source()));
+                let kind = if *registered { "multi-input, registered" } else { "multi-input" };
+                report.push_str(&format!("| {} | {} | {} | {} | {} |\n", code(name), sources.join(", "), code(to), kind, body));
+            }
+            Statement::Library { name, element, from, to } => {
+                let sources: Vec<String> = from.iter().map(|object| code(object)).collect();
+                report.push_str(&format!("| {} | {} | {} | library | {} |\n", code(name), sources.join(", "), code(to), code(&format!("std.{}", element))));
+            }
+            Statement::Constant { name, to, value } => {
+                report.push_str(&format!("| {} | {} | {} | constant | {} |\n", code(name), code(UNIT_OBJECT), code(to), code(value)));
+            }
+            Statement::Tensor { name, factors } => {
+                let (from, to) = morphisms.get(name).map_or(("?".to_string(), "?".to_string()), |(from, to)| (code(from), code(to)));
+                report.push_str(&format!("| {} | {} | {} | tensor | {} |\n", code(name), from, to, code(&factors.join(" ⊗ "))));
+            }
+            Statement::Swap { name, first, second } => {
+                let (from, to) = morphisms.get(name).map_or(("?".to_string(), "?".to_string()), |(from, to)| (code(from), code(to)));
+                report.push_str(&format!("| {} | {} | {} | swap | {} |\n", code(name), from, to, code(&format!("swap({}, {})", first, second))));
+            }
+            Statement::Dup { name, object } => {
+                let to = morphisms.get(name).map_or("?".to_string(), |(_, to)| code(to));
+                report.push_str(&format!("| {} | {} | {} | duplication | {} |\n", code(name), code(object), to, code(&format!("dup({})", object))));
+            }
+            Statement::Projection { name, product, index } => {
+                let to = morphisms.get(name).map_or("?".to_string(), |(_, to)| code(to));
+                report.push_str(&format!("| {} | {} | {} | projection | {} |\n", code(name), code(product), to, code(&format!("pi{}({})", index, product))));
+            }
+            Statement::Injection { name, coproduct, right } => {
+                let from = morphisms.get(name).map_or("?".to_string(), |(from, _)| code(from));
+                let side = if *right { "inr" } else { "inl" };
+                report.push_str(&format!("| {} | {} | {} | injection | {} |\n", code(name), from, code(coproduct), code(&format!("{}({})", side, coproduct))));
+            }
+            Statement::Trace { name, step } => {
+                // The loop reads the step's target back, so only its other inputs remain
+                let (sources, to) = ast.statements.iter().find_map(|stmt| match stmt {
+                    Statement::Morphism { name, from, to, .. } if name == step => Some((vec![from], to)),
+                    Statement::MultiInput { name, from, to, .. } if name == step => Some((from.iter().collect(), to)),
+                    _ => None,
+                }).map_or(("?".to_string(), "?".to_string()), |(sources, to)| {
+                    let sources: Vec<String> = sources.into_iter().filter(|object| *object != to).map(|object| code(object)).collect();
+                    (if sources.is_empty() { code(UNIT_OBJECT) } else { sources.join(", ") }, code(to))
+                });
+                report.push_str(&format!("| {} | {} | {} | trace | {} |\n", code(name), sources, to, code(&format!("trace({})", step))));
+            }
+            Statement::Functor { name, from, to, .. } => {
+                report.push_str(&format!("| {} | {} | {} | functor | images of every morphism |\n", code(name), code(from), code(to)));
+            }
+            Statement::Nat { name, from, to, .. } => {
+                report.push_str(&format!("| {} | {} | {} | natural transformation | one adapter per object |\n", code(name), code(from), code(to)));
+            }
+            Statement::Case { name, from, to, branches } => {
+                let body = code(&format!("[{}]", branches.join(", ")));
+                report.push_str(&format!("| {} | {} | {} | case analysis | {} |\n", code(name), code(from), code(to), body));
+            }
+            Statement::Table { name, from, to, rows } => {
+                report.push_str(&format!("| {} | {} | {} | truth table | {} rows |\n", code(name), code(from), code(to), rows.len()));
+            }
+            Statement::Verilog { name, from, to, .. } => {
+                report.push_str(&format!("| {} | {} | {} | inline Verilog | written by hand |\n", code(name), code(from), code(to)));
+            }
+            Statement::Rom { name, from, to, path } => {
+                report.push_str(&format!("| {} | {} | {} | ROM | {} |\n", code(name), code(from), code(to), code(path)));
+            }
+            // Everything else declares no morphism, listed so a new kind of
+            // statement has to decide whether it gets a row
+            Statement::Object { .. }
+            | Statement::ObjectFamily { .. }
+            | Statement::Wire { .. }
+            | Statement::Interface { .. }
+            | Statement::AssertCommute { .. }
+            | Statement::AssertEqual { .. }
+            | Statement::AssertKind { .. }
+            | Statement::Param { .. }
+            | Statement::Test { .. }
+            | Statement::Product { .. }
+            | Statement::Coproduct { .. }
+            | Statement::Enum { .. }
+            | Statement::Memory { .. }
+            | Statement::Import { .. }
+            | Statement::Template { .. }
+            | Statement::Use { .. }
+            | Statement::Clock { .. }
+            | Statement::Top { .. }
+            | Statement::Pragma { .. }
+            | Statement::Category { .. } => {}
+        }
+    }
+
+    report.push_str("\n## Assertions\n\n");
+    let assertions = ast.get_commute_assertions();
+    let equalities = ast.get_equal_assertions();
+    let kinds = ast.get_kind_assertions();
+    if assertions.is_empty() && equalities.is_empty() && kinds.is_empty() {
+        report.push_str("None\n");
+    }
+    for (lhs, rhs) in assertions {
+        report.push_str(&format!("- {}\n", code(&format!("{} == {}", lhs.join(" ∘ "), rhs.join(" ∘ ")))));
+    }
+    for (lhs, rhs) in equalities {
+        report.push_str(&format!("- {} (equality)\n", code(&format!("{} == {}", lhs, rhs))));
+    }
+    for (morphism, kind) in kinds {
+        report.push_str(&format!("- {} is {}\n", code(morphism), kind.keyword()));
+    }
+
+    let stats = dag.stats();
+    report.push_str("\n## DAG\n\n| Metric | Value |\n|---|---|\n");
+    for (metric, value) in [
+        ("Objects", stats.objects),
+        ("Morphisms", stats.morphisms),
+        ("Edges", stats.edges),
+        ("Inputs", stats.inputs),
+        ("Outputs", stats.outputs),
+    ] {
+        report.push_str(&format!("| {} | {} |\n", metric, value));
+    }
+    let depth = stats.depth.map_or("cyclic".to_string(), |d| d.to_string());
+    report.push_str(&format!("| Depth | {} |\n", depth));
+    report
+}
+
+/// Inline code span that is safe inside a table cell
+fn code(text: &str) -> String {
+    format!("`{}`", text.replace('|', "\\|"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_with_report() {
+        let source = "object A : 4\nobject B : 5\nobject C\nmorphism f: A -> B = in + 1\nmorphism g: B -> C = in | 1\nmorphism h: A -> C\nassert commute: g ∘ f == h\n";
+        let (verilog, report) = compile_with_report(source).unwrap();
+        assert!(verilog.contains("module top ("));
+        assert!(report.contains("| `A` | 4 | input |\n"));
+        assert!(report.contains("| `B` | 5 | internal |\n"));
+        assert!(report.contains("| `C` | 8 | output |\n"));
+        assert!(report.contains("| `f` | `A` | `B` | combinational | `in + 1` |\n"));
+        assert!(report.contains("| `g` | `B` | `C` | combinational | `in \\| 1` |\n"));
+        assert!(report.contains("| `h` | `A` | `C` | combinational | placeholder |\n"));
+        assert!(report.contains("- `g ∘ f == h`\n"));
+        assert!(report.contains("| Depth | 2 |\n"));
+
+        assert!(compile_with_report("object A\nbogus").is_err());
+    }
+}
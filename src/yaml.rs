@@ -0,0 +1,220 @@
+//! YAML front-end producing the same AST as the DSL parser. Enabled by the
+//! `yaml` feature.
+//!
+//! ```yaml
+//! clocks: [clk_a]
+//! objects:
+//!   - { name: A, width: 8, domain: clk_a }
+//!   - { name: B, width: A + 1, signed: true }
+//!   - { name: Q, width: fixed<8,4> }
+//! morphisms:
+//!   - { name: f, from: A, to: B, body: in + 1 }
+//! asserts:
+//!   - { lhs: [g, f], rhs: [h] }
+//! ```
+
+use nom::combinator::all_consuming;
+use nom::IResult;
+use serde::Deserialize;
+
+use crate::ast::{CategoryAST, Composition, Expr, Statement, WidthExpr};
+use crate::parser::{expr, object_width, verilog_number, width_expr};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Document {
+    #[serde(default)]
+    clocks: Vec<String>,
+    #[serde(default)]
+    objects: Vec<ObjectEntry>,
+    #[serde(default)]
+    morphisms: Vec<MorphismEntry>,
+    #[serde(default)]
+    asserts: Vec<AssertEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObjectEntry {
+    name: String,
+    width: Option<Scalar>,
+    #[serde(default)]
+    signed: bool,
+    initial: Option<Scalar>,
+    #[serde(default)]
+    active_low: bool,
+    domain: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MorphismEntry {
+    name: String,
+    from: String,
+    to: String,
+    from_width: Option<Scalar>,
+    to_width: Option<Scalar>,
+    body: Option<Scalar>,
+    lanes: Option<usize>,
+    #[serde(default)]
+    registered: bool,
+    guard: Option<String>,
+    external: Option<String>,
+    #[serde(default)]
+    handshake: bool,
+    #[serde(default)]
+    maybe: bool,
+    generic: Option<String>,
+}
+
+/// Both sides of a commutativity assertion, outermost morphism first like `∘`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AssertEntry {
+    lhs: Vec<String>,
+    rhs: Vec<String>,
+}
+
+/// A plain YAML number, or text written in DSL syntax
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Scalar {
+    Number(u64),
+    Text(String),
+}
+
+impl Scalar {
+    fn text(&self) -> String {
+        match self {
+            Scalar::Number(n) => n.to_string(),
+            Scalar::Text(text) => text.clone(),
+        }
+    }
+}
+
+impl CategoryAST {
+    /// Build an AST from a YAML document with `clocks`, `objects`,
+    /// `morphisms` and `asserts` lists. Widths, bodies and initial values use
+    /// DSL syntax
+    pub fn from_yaml(source: &str) -> Result<Self, String> {
+        let document: Document = serde_yaml::from_str(source).map_err(|e| format!("YAML error: {}", e))?;
+        let mut statements: Vec<Statement> = document.clocks.into_iter().map(|name| Statement::Clock { name }).collect();
+        for object in document.objects {
+            let (width, fraction) = match &object.width {
+                Some(Scalar::Text(text)) => parse_all(object_width, text, "width", &object.name).map(|(w, f)| (Some(w), f))?,
+                Some(w) => (Some(width(w, &object.name)?), None),
+                None => (None, None),
+            };
+            statements.push(Statement::Object {
+                width,
+                initial: object.initial.map(|i| parse_all(verilog_number, &i.text(), "initial value", &object.name)).transpose()?,
+                name: object.name,
+                signed: object.signed,
+                active_low: object.active_low,
+                fraction,
+                domain: object.domain,
+            });
+        }
+        for morphism in document.morphisms {
+            statements.push(Statement::Morphism {
+                from_width: morphism.from_width.map(|w| width(&w, &morphism.name)).transpose()?,
+                to_width: morphism.to_width.map(|w| width(&w, &morphism.name)).transpose()?,
+                body: morphism.body.map(|b| body(&b, &morphism.name)).transpose()?,
+                bindings: Vec::new(),
+                name: morphism.name,
+                from: morphism.from,
+                to: morphism.to,
+                lanes: morphism.lanes,
+                registered: morphism.registered,
+                guard: morphism.guard,
+                external: morphism.external,
+                handshake: morphism.handshake,
+                maybe: morphism.maybe,
+                generic: morphism.generic,
+                attributes: Vec::new(),
+            });
+        }
+        for assertion in document.asserts {
+            if assertion.lhs.is_empty() || assertion.rhs.is_empty() {
+                return Err("YAML error: assertion chains must not be empty".to_string());
+            }
+            statements.push(Statement::AssertCommute {
+                lhs: Composition::from_chain(assertion.lhs),
+                rhs: Composition::from_chain(assertion.rhs),
+            });
+        }
+        Ok(CategoryAST { statements })
+    }
+}
+
+fn width(scalar: &Scalar, owner: &str) -> Result<WidthExpr, String> {
+    match scalar {
+        Scalar::Number(n) => Ok(WidthExpr::Const(*n as usize)),
+        Scalar::Text(text) => parse_all(width_expr, text, "width", owner),
+    }
+}
+
+fn body(scalar: &Scalar, owner: &str) -> Result<Expr, String> {
+    match scalar {
+        Scalar::Number(n) => Ok(Expr::Literal(*n)),
+        Scalar::Text(text) => parse_all(expr, text, "body", owner),
+    }
+}
+
+/// Run a DSL parser over a whole YAML string value
+fn parse_all<T>(parser: fn(&str) -> IResult<&str, T>, text: &str, what: &str, owner: &str) -> Result<T, String> {
+    all_consuming(parser)(text.trim())
+        .map(|(_, value)| value)
+        .map_err(|_| format!("YAML error: invalid {} `{}` of {}", what, text, owner))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_category_file;
+
+    #[test]
+    fn test_from_yaml_matches_dsl() {
+        let yaml = "
+clocks: [clk_a]
+objects:
+  - { name: A, width: 8 }
+  - { name: B, width: A + 1, signed: true }
+  - { name: C, initial: 8'h3C }
+  - name: rst_n
+    width: 1
+    active_low: true
+  - { name: Q, width: 'fixed<8,4>', signed: true, domain: clk_a }
+morphisms:
+  - { name: f, from: A, to: B, body: in + 1 }
+  - { name: g, from: B, to: C, body: 0, registered: true }
+  - { name: h, from: A, to: C, to_width: 8, guard: DEBUG }
+  - { name: lane, from: A, to: C, lanes: 4 }
+asserts:
+  - { lhs: [g, f], rhs: [h] }
+";
+        let dsl = "clock clk_a
+object A : 8
+object B : signed A + 1
+object C = 8'h3C
+object rst_n : 1 active_low
+object Q : signed fixed<8,4> @ clk_a
+morphism f: A -> B = in + 1
+seq morphism g: B -> C = 0
+morphism h: A -> C : 8 @ifdef DEBUG
+morphism lane[0..4]: A -> C
+assert commute: g ∘ f == h
+";
+        let (rest, expected) = parse_category_file(dsl).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(CategoryAST::from_yaml(yaml).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_from_yaml_errors() {
+        let bad_width = CategoryAST::from_yaml("objects:\n  - { name: A, width: 8 + }\n").unwrap_err();
+        assert!(bad_width.contains("invalid width `8 +` of A"), "{}", bad_width);
+        assert!(CategoryAST::from_yaml("wires: []\n").unwrap_err().contains("unknown field `wires`"));
+        assert!(CategoryAST::from_yaml("asserts:\n  - { lhs: [], rhs: [h] }\n").is_err());
+    }
+}